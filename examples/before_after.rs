@@ -114,39 +114,25 @@ fn my_transformer(
     let sum = decoder_input.apply_fn(|t| t + encoder_input.tensor(), params)?;
 
     // The transformer function returns a TransformerOutput. So the result must match the size
-    // expected by the TransformerOutput. For this demo, we'll just drop the second dimension of the
-    // tensor. Here, we illustrate a series of tch::Tensor operations performed without TensorTypes,
-    // then wrapped into the return value.
-    let cos = sum.tensor().cos(); // `cos` is type tch::Tensor
-    let narrowed = cos.narrow(2, 0, 1); // `narrowed` is type tch::Tensor
-    let squeezed = narrowed.squeeze(); // `squeezed` is type tch::Tensor
-
-    // We've now dropped a dimension of the tensor. The tch::Tensor representation is flexible and
-    // also represents the differently-shaped tensor. But it provides no type safety which can make
-    // it hard to find where dimension changes occurred in the code. Instead, here, we'll wrap the
-    // tensor in a TransformerOutput, which we've defined above with a specific shape that will be
-    // checked by the new() function.
-    let transformer_out = TransformerOutput::new(squeezed, params)?;
+    // expected by the TransformerOutput. For this demo, we'll just drop the last dimension of the
+    // tensor. The apply_into() function applies a series of tch::Tensor operations that change the
+    // shape, then wraps the result in the target type, whose shape is checked.
+    //
+    // The tch::Tensor representation is flexible and also represents the differently-shaped
+    // tensor. But it provides no type safety which can make it hard to find where dimension changes
+    // occurred in the code. Naming the target type makes the intended shape change explicit.
+    let transformer_out: TransformerOutput =
+        sum.apply_into(|t| t.cos().narrow(2, 0, 1).squeeze_dim(2), params)?;
     Ok(transformer_out)
 }
 
-// The make_encoder_input_typed() function, as before, just creates a tch::Tensor with random
-// values. But this one accepts and returns typed tensors. The compiler will catch any errors in the
-// argument order or return value shape.
-fn make_encoder_input_typed(
-    batch_size: BatchSize,
-    sequence_length: SequenceLength,
-    model_dimension: ModelDimension,
-    params: &Params,
-) -> Result<EncoderInput> {
-    let t = Tensor::rand(
-        [*batch_size, *sequence_length, *model_dimension],
-        (Kind::Float, Device::Cpu),
-    );
-    // Return a new EncoderInput with the tch::Tensor.
+// The make_encoder_input_typed() function, as before, just creates a tensor with random values. But
+// this one returns a typed tensor. Because the EncoderInput type already knows its shape from the
+// params, randn() builds a tensor of the right size without repeating the dimensions.
+fn make_encoder_input_typed(params: &Params) -> Result<EncoderInput> {
     // Note that the result is wrapped in Ok() to convert from the TensorTypeError to an
     // anyhow::Error use by the anyhow::Result type.
-    Ok(EncoderInput::new(t, params)?)
+    Ok(EncoderInput::randn(params, Device::Cpu)?)
 }
 
 fn make_decoder_input_typed(
@@ -178,8 +164,7 @@ fn after(
         model_dimension,
     };
 
-    let encoder_input =
-        make_encoder_input_typed(batch_size, sequence_length, model_dimension, &params)?;
+    let encoder_input = make_encoder_input_typed(&params)?;
     let decoder_input =
         make_decoder_input_typed(batch_size, sequence_length, model_dimension, &params)?;
 
@@ -214,6 +214,7 @@ fn error_handling() -> Result<(), Error> {
                 type_name,
                 expected,
                 found,
+                ..
             } => {
                 println!(
                     "new() failed as expected with a ShapeMismatch error on type {}: expected {:?}, but found {:?}",
@@ -0,0 +1,180 @@
+use crate::{
+    InvalidMutAction, TensorMut, TensorRef, TensorType, TensorTypeError, TensorTypeOptions,
+};
+
+/// A `Float` tensor of shape `[B, S, D]`, with the dimensions given as const generics rather than
+/// by a parameters struct. This suits shapes known at compile time, such as in tests or for fixed
+/// architectures. As for the types declared with the macro, each dimension must be at least one.
+///
+/// ConstTensor implements TensorType with `()` as its parameters type, so it can be used with the
+/// other tensor types, e.g. `x.reshape_to::<ConstTensor<2, 3, 4>>(&())`.
+///
+/// # Example
+/// ```
+/// use tensor_types::ConstTensor;
+/// use tch::{Device, Kind, Tensor};
+///
+/// let t = Tensor::zeros([2, 3, 4], (Kind::Float, Device::Cpu));
+/// let x = ConstTensor::<2, 3, 4>::new(t)?;
+/// assert_eq!(x.size(), &[2, 3, 4]);
+/// # Ok::<(), tensor_types::TensorTypeError>(())
+/// ```
+pub struct ConstTensor<const B: usize, const S: usize, const D: usize> {
+    tensor: tch::Tensor,
+}
+
+impl<const B: usize, const S: usize, const D: usize> ConstTensor<B, S, D> {
+    /// The dimensions of the tensor.
+    pub const SIZE: [i64; 3] = [B as i64, S as i64, D as i64];
+
+    /// Wrap a tensor, checking that its shape is `[B, S, D]` and its kind is `Float`.
+    pub fn new(tensor: tch::Tensor) -> Result<Self, TensorTypeError> {
+        Self::check(&tensor)?;
+        Ok(Self { tensor })
+    }
+
+    /// Check that the shape of a tensor is `[B, S, D]` and its kind is `Float`, returning a
+    /// ShapeMismatch or KindMismatch error if not, or a Multiple error if neither is. An
+    /// InvalidDimension error is returned if a dimension is zero.
+    pub fn check(tensor: &tch::Tensor) -> Result<(), TensorTypeError> {
+        let expected = Self::checked_size()?;
+        let mut errors = Vec::new();
+        let found = tensor.size();
+        if found != expected {
+            errors.push(TensorTypeError::ShapeMismatch {
+                type_name: Self::NAME.to_string(),
+                dim_names: vec!["B".to_string(), "S".to_string(), "D".to_string()],
+                expected: expected.to_vec(),
+                found,
+            });
+        }
+        if tensor.kind() != tch::Kind::Float {
+            errors.push(TensorTypeError::KindMismatch {
+                type_name: Self::NAME.to_string(),
+                expected: Self::KINDS.to_vec(),
+                found: tensor.kind(),
+            });
+        }
+        match errors.len() {
+            0 => Ok(()),
+            1 => Err(errors.remove(0)),
+            _ => Err(TensorTypeError::Multiple(errors)),
+        }
+    }
+
+    /// Return the dimensions, or an InvalidDimension error if one is zero.
+    fn checked_size() -> Result<[i64; 3], TensorTypeError> {
+        if let Some(axis) = Self::SIZE.iter().position(|&dim| dim < 1) {
+            return Err(TensorTypeError::InvalidDimension {
+                type_name: Self::NAME.to_string(),
+                axis,
+                value: Self::SIZE[axis],
+            });
+        }
+        Ok(Self::SIZE)
+    }
+}
+
+impl<const B: usize, const S: usize, const D: usize> TensorType for ConstTensor<B, S, D> {
+    type InnerType = ();
+
+    const NAME: &'static str = "ConstTensor";
+
+    const KINDS: &'static [tch::Kind] = &[tch::Kind::Float];
+
+    const OPTIONS: TensorTypeOptions = TensorTypeOptions::DEFAULT;
+
+    fn new(tensor: tch::Tensor, _params: &()) -> Result<Self, TensorTypeError> {
+        Self::new(tensor)
+    }
+
+    fn new_ref<'a>(
+        tensor: &'a tch::Tensor,
+        _params: &(),
+    ) -> Result<TensorRef<'a, Self>, TensorTypeError> {
+        Self::check(tensor)?;
+        Ok(TensorRef::new(tensor))
+    }
+
+    fn verify(&self, _params: &()) -> Result<(), TensorTypeError> {
+        Self::check(&self.tensor)
+    }
+
+    fn check_shape(tensor: &tch::Tensor, _params: &()) -> Result<(), TensorTypeError> {
+        Self::check(tensor)
+    }
+
+    fn new_unchecked(tensor: tch::Tensor) -> Self {
+        Self { tensor }
+    }
+
+    fn tensor(&self) -> &tch::Tensor {
+        &self.tensor
+    }
+
+    fn tensor_mut<'a>(&'a mut self, params: &'a ()) -> TensorMut<'a, ()> {
+        TensorMut::new(
+            &mut self.tensor,
+            params,
+            |tensor, _| Self::check(tensor),
+            InvalidMutAction::Panic,
+        )
+    }
+
+    fn apply_fn<F>(&self, tfn: F, _params: &()) -> Result<Self, TensorTypeError>
+    where
+        F: FnOnce(&tch::Tensor) -> tch::Tensor,
+    {
+        Self::new(tfn(&self.tensor))
+    }
+
+    fn clone(&self, _params: &()) -> Result<Self, TensorTypeError> {
+        Self::new(self.tensor.shallow_clone())
+    }
+
+    fn into_inner(self) -> tch::Tensor {
+        self.tensor
+    }
+
+    fn creation_kind(_params: &()) -> Result<tch::Kind, TensorTypeError> {
+        Ok(tch::Kind::Float)
+    }
+
+    fn expected_size(_params: &()) -> Result<Vec<i64>, TensorTypeError> {
+        Ok(Self::checked_size()?.to_vec())
+    }
+}
+
+impl<const B: usize, const S: usize, const D: usize> std::fmt::Debug for ConstTensor<B, S, D> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "\"ConstTensor<{B}, {S}, {D}>\"({:?})", self.tensor)
+    }
+}
+
+impl<const B: usize, const S: usize, const D: usize> std::ops::Deref for ConstTensor<B, S, D> {
+    type Target = tch::Tensor;
+
+    fn deref(&self) -> &Self::Target {
+        &self.tensor
+    }
+}
+
+impl<const B: usize, const S: usize, const D: usize> AsRef<tch::Tensor> for ConstTensor<B, S, D> {
+    fn as_ref(&self) -> &tch::Tensor {
+        &self.tensor
+    }
+}
+
+impl<const B: usize, const S: usize, const D: usize> std::borrow::Borrow<tch::Tensor>
+    for ConstTensor<B, S, D>
+{
+    fn borrow(&self) -> &tch::Tensor {
+        &self.tensor
+    }
+}
+
+impl<const B: usize, const S: usize, const D: usize> From<ConstTensor<B, S, D>> for tch::Tensor {
+    fn from(value: ConstTensor<B, S, D>) -> Self {
+        value.tensor
+    }
+}
@@ -21,10 +21,99 @@
 //! ```
 //! Here, `MyTensor` is the name of the new type that will wrap a tch::Tensor, `[size1, size2,
 //! size3]` is a list of the fields in the `Params` type that gives the sizes of your tensor, and
-//! `tch::Kind::Float` is the kind of the tensor.
+//! `tch::Kind::Float` is the kind of the tensor. A list of kinds such as `[tch::Kind::Float,
+//! tch::Kind::Double]` may be given instead, in which case a tensor of any of those kinds is
+//! accepted. `KindClass::AnyFloat` or `KindClass::AnyInt` accepts any floating point or integer
+//! kind.
+//!
+//! The kind may also be given by a field of the `Params` type, as in `kind = compute_kind`, where
+//! `compute_kind` is a `tch::Kind` field, so that the precision can be chosen at runtime. Similarly,
+//! `kinds = allowed_kinds`, where `allowed_kinds` is a `Vec<tch::Kind>` field, accepts any kind in
+//! a set read from the runtime configuration.
+//!
+//! A dimension may also be a sum of products of fields, such as `[batch_size, seq_len * d_model]`
+//! for a flattened tensor. An empty list, `[]`, declares a scalar: a tensor of rank 0. A dimension
+//! may be given inclusive bounds, as in `[batch_size in 1..=4096, seq_len]`, so that an
+//! out-of-range value in the parameters is reported when a tensor is checked.
+//!
+//! Marking the first field as optional, as in `[batch_size?, seq_len, d_model]`, accepts tensors
+//! with or without the leading dimension, such as both batched inputs and single examples.
+//!
+//! Dimensions that come from floating point calculations can be given by `f32` or `f64` fields by
+//! writing `rounded [length, length * ratio]`. Each dimension is rounded to the nearest integer,
+//! and an error is returned if it isn't a whole number.
+//!
+//! The parameters type may be a path, such as `crate::config::Params`. To keep its fields private,
+//! write `getters [batch_size, seq_len]`, and the dimensions are read by calling
+//! `params.batch_size()` and `params.seq_len()` instead. The getters may also be methods of a
+//! trait, with a trait object as the parameters type, as in `tensor_type!(MyTensor, getters
+//! [batch_size, seq_len], dyn HasDims, Kind::Float)`. Any type implementing the trait can then be
+//! passed as the parameters, so one type can be checked against several configuration structs.
+//! The getters are called only when a tensor is checked, so a dimension that's costly to compute
+//! can be computed on demand. A list of simple calls may also be written as calls, as in
+//! `[batch_size(), seq_len()]`. A dimension held in a closure field is read through a getter that
+//! calls it, as in `fn batch_size(&self) -> i64 { (self.batch_size)() }`.
+//!
+//! While prototyping, before the dimensions are pinned down, the list of fields may be replaced by
+//! `rank = 3` to check only the number of dimensions of the tensor.
+//!
+//! For library code whose shapes are known only at runtime, `tensor_type!(DynTensor, dynamic,
+//! tch::Kind::Float)` declares a type without a `Params` struct. Its `new()` takes the expected
+//! shape as a slice, as in `DynTensor::new(tensor, &[2, 3])`, and the kind is still checked.
+//!
+//! The `tensor_types!` macro declares several types sharing one parameters type, as in
+//! `tensor_types! { Params; BatchSeq => [batch_size, seq_len]: Kind::Int64; }`.
+//!
+//! Options may follow the kind as `name = value` pairs. For example, `broadcast = true` accepts
+//! tensors whose leading dimensions are absent, such as a bias that broadcasts against the full
+//! shape. See `TensorTypeOptions` for the available options.
+//!
+//! The wrapped tensor is a private field, reached through `tensor()`, `tensor_mut()`, and
+//! `into_inner()`, so that code outside the defining module can't replace it with a tensor of the
+//! wrong shape. Writing `#[public_tensor]` before the name, as in `tensor_type!(#[public_tensor]
+//! MyTensor, ...)`, makes the field public for code that relies on it.
+//!
+//! Writing `#[partial_eq]` before the name implements PartialEq, comparing the kinds, devices,
+//! shapes, and elements of the wrapped tensors. It's opt-in so that a type can implement its own.
+//! For floating point tensors, prefer `approx_eq()`, which compares within a tolerance.
+//!
+//! Writing `#[shape_eq]` before the name instead implements PartialEq, Eq, and Hash by comparing
+//! the keys returned by `shape_key()`, the type name, shape, and kind, so that values can be the
+//! keys of a `HashMap` or `HashSet`. Values with different elements are then equal.
+//!
+//! `tensor_type!` generates only the named struct and its impls, with no other named items, so
+//! types of the same name declared in different crates or modules don't collide. To use both,
+//! import them under aliases, as in `use encoder::Embedding as EncoderEmbedding;`, or refer to them
+//! by path. A library can keep its types in a module of their own by invoking the macro inside it,
+//! as in `pub mod layers { tensor_type!(Embedding, [vocab, d_model], super::Params, Kind::Float); }`,
+//! and re-export them under unique names, as in `pub use layers::Embedding as LayerEmbedding;`.
+//! The `NAME` used in error messages is the declared name, not the alias.
+//!
+//! Tensor types implement Serialize and Deserialize, writing the kind, shape, and elements of the
+//! tensor. Deserialize can't check the tensor, since the parameters aren't available, so **a
+//! deserialized value isn't checked**: call `verify()` on it, or deserialize with
+//! `TensorType::seed(&params)`, which checks it as `new()` does.
+//!
+//! For shapes known at compile time, `ConstTensor<B, S, D>` gives the dimensions of a `Float`
+//! tensor as const generics instead, so that no `Params` struct is needed.
+//!
+//! To follow tensors through a program, enable the `trace` feature. Each successful `new()` then
+//! logs the type name, shape, kind, and device at the debug level through the `log` crate, and
+//! an invalid mutation through `tensor_mut()` with the `Warn` action is logged at the warn level.
+//!
+//! To track down where the precision of a value changes, enable the `cast-history` feature. Each
+//! value then records the kinds its tensor has had through `apply_fn()` and `to_kind_as()`,
+//! returned by `kind_history()`. Without the feature, the history is always empty and costs
+//! nothing.
+//!
+//! The `unchecked-release` feature makes `new()` skip its checks in builds without debug
+//! assertions, such as release builds, so that they cost nothing in production. `new()` still
+//! returns a `Result`, but a tensor of the wrong shape or kind is then wrapped without an error,
+//! breaking the guarantees of the type. Enable it only for code whose types are exercised by
+//! debug builds and tests.
 //!
 //! 2. The `Params` struct can be defined however you like, so long as it provides the required
-//! fields. So for example
+//!    fields. So for example
 //! ```rust
 //!    pub struct Params {
 //!       size1: i64,
@@ -33,7 +122,10 @@
 //!    };
 //! ```
 //! The fields can be anything that can be cast to into an `i64`, which is the type used for
-//! tch:Tensor dimensions.
+//! tch:Tensor dimensions, or any integer or parameter type. A value out of the range of `i64`,
+//! such as a large `u64`, gives a DimensionOverflow error. A field that's missing from the
+//! `Params` type, such as a misspelled `batch_sze`, is a compile error reported at the field name
+//! in the `tensor_type` call, as ``no field `batch_sze` on type `&Params` ``.
 //!  
 //! The tensor_types crates also provides a `parameter_type` macro to make this easy and create
 //! typed parameters, allowing the compiler to catch mixing up i64s. So, preferred, define types for
@@ -55,9 +147,9 @@
 //! ```
 //!
 //! 3. With these definitions in place, we can start using them at runtime. Somewhere near the start
-//! of your program, instantiate the parameters structure so the tensor dimensions are available.
-//! This happens at runtime for for loading from a configuration file and for flexibility in
-//! testing.
+//!    of your program, instantiate the parameters structure so the tensor dimensions are available.
+//!    This happens at runtime for for loading from a configuration file and for flexibility in
+//!    testing.
 //! ```rust
 //! # use tensor_types::parameter_type;
 //! # parameter_type!(BatchSize, i64);
@@ -113,8 +205,39 @@
 //! # Ok(())
 //! # }
 
+pub use const_tensor::ConstTensor;
+pub use parameter_types::DimensionValue;
+pub use parameter_types::ParameterParseError;
+pub use tensor_serde::TensorSeed;
+pub use tensor_types::InvalidMutAction;
+pub use tensor_types::KindClass;
+pub use tensor_types::ShapeKey;
+pub use tensor_types::TensorMut;
+pub use tensor_types::TensorRef;
 pub use tensor_types::TensorType;
 pub use tensor_types::TensorTypeError;
+pub use tensor_types::TensorTypeOptions;
+pub use tensor_types::TypedTensorError;
+
+#[cfg(feature = "num-format")]
+#[doc(hidden)]
+pub use num_format;
+#[cfg(feature = "num-traits")]
+#[doc(hidden)]
+pub use num_traits;
+#[doc(hidden)]
+pub use parameter_types::fmt_parameter;
+#[doc(hidden)]
+pub use serde;
+#[doc(hidden)]
+pub use tensor_serde::{deserialize_tensor, serialize_tensor};
+#[doc(hidden)]
+pub use tensor_types::{
+    checked_binary_op, checked_dimension, trace_new, CheckedDimension, Dimension, KindHistory,
+    UNCHECKED_RELEASE,
+};
 
+mod const_tensor;
 mod parameter_types;
+mod tensor_serde;
 mod tensor_types;
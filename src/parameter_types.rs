@@ -1,3 +1,140 @@
+/// Integer types that can give the value of a tensor dimension. The parameter types created with
+/// the `parameter_type!` macro use this trait to convert their inner values into `i64`, the type
+/// used for tch::Tensor dimensions, and implement it themselves, so that tensor types can report a
+/// value out of the range of `i64` as a DimensionOverflow error.
+///
+/// The conversion panics for values larger than `i64::MAX`, which can only occur for the unsigned
+/// 64-bit and the 128-bit types.
+pub trait DimensionValue: Copy {
+    /// Convert the value into a tch::Tensor dimension.
+    fn to_dimension(self) -> i64;
+
+    /// Convert the value into a tch::Tensor dimension, or return None if it's out of the range of
+    /// `i64`.
+    fn try_to_dimension(self) -> Option<i64>;
+
+    /// Convert the value into a tch::Tensor dimension, clamping it to the range of `i64`.
+    fn to_dimension_saturating(self) -> i64;
+}
+
+macro_rules! impl_dimension_value {
+    ($($int:ty),*) => {
+        $(
+            impl DimensionValue for $int {
+                fn to_dimension(self) -> i64 {
+                    i64::try_from(self).expect("parameter value exceeds i64::MAX")
+                }
+
+                fn try_to_dimension(self) -> Option<i64> {
+                    i64::try_from(self).ok()
+                }
+
+                fn to_dimension_saturating(self) -> i64 {
+                    i64::try_from(self).unwrap_or(if self > 0 { i64::MAX } else { i64::MIN })
+                }
+            }
+        )*
+    };
+}
+
+impl_dimension_value!(i8, i16, i32, i64, i128, isize, u8, u16, u32, u64, u128, usize);
+
+/// The error returned when parsing a parameter type from a string fails.
+#[derive(Debug)]
+pub struct ParameterParseError {
+    pub type_name: String,
+    pub value: String,
+    pub source: std::num::ParseIntError,
+}
+
+impl std::fmt::Display for ParameterParseError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(
+            f,
+            "invalid value {:?} for parameter type {}: {}",
+            self.value, self.type_name, self.source
+        )
+    }
+}
+
+impl std::error::Error for ParameterParseError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.source)
+    }
+}
+
+/// Writes a parameter value for the Display impl of a parameter type. With the default
+/// `num-format` feature, thousands separators are inserted, as in `1,280`.
+#[cfg(feature = "num-format")]
+#[doc(hidden)]
+pub fn fmt_parameter<T: num_format::ToFormattedString>(
+    value: &T,
+    f: &mut std::fmt::Formatter<'_>,
+) -> std::fmt::Result {
+    write!(f, "{}", value.to_formatted_string(&num_format::Locale::en))
+}
+
+/// Writes a parameter value for the Display impl of a parameter type. Without the `num-format`
+/// feature, the plain number is written, as in `1280`.
+#[cfg(not(feature = "num-format"))]
+#[doc(hidden)]
+pub fn fmt_parameter<T: std::fmt::Display>(
+    value: &T,
+    f: &mut std::fmt::Formatter<'_>,
+) -> std::fmt::Result {
+    write!(f, "{}", value)
+}
+
+/// Implements Add, Mul, and the `num_traits` Zero and One traits for a parameter type, delegating
+/// to the inner type. With the `num-traits` feature disabled, this generates nothing. The feature
+/// is tested here rather than in parameter_type!, whose expansion would test the features of the
+/// calling crate.
+#[cfg(feature = "num-traits")]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __parameter_type_num_traits {
+    ($type_name:ident, $inner_type:ty) => {
+        impl std::ops::Add for $type_name {
+            type Output = Self;
+
+            fn add(self, rhs: Self) -> Self {
+                $type_name(self.0 + rhs.0)
+            }
+        }
+
+        impl std::ops::Mul for $type_name {
+            type Output = Self;
+
+            fn mul(self, rhs: Self) -> Self {
+                $type_name(self.0 * rhs.0)
+            }
+        }
+
+        impl $crate::num_traits::Zero for $type_name {
+            fn zero() -> Self {
+                $type_name(<$inner_type as $crate::num_traits::Zero>::zero())
+            }
+
+            fn is_zero(&self) -> bool {
+                $crate::num_traits::Zero::is_zero(&self.0)
+            }
+        }
+
+        impl $crate::num_traits::One for $type_name {
+            fn one() -> Self {
+                $type_name(<$inner_type as $crate::num_traits::One>::one())
+            }
+        }
+    };
+}
+
+#[cfg(not(feature = "num-traits"))]
+#[doc(hidden)]
+#[macro_export]
+macro_rules! __parameter_type_num_traits {
+    ($type_name:ident, $inner_type:ty) => {};
+}
+
 /// The parameter_type! macro defines a new named type with a specific inner type.
 ///
 /// This macro generates a new struct type with the specified name and inner type, along with
@@ -6,7 +143,10 @@
 /// # Arguments
 ///
 /// * `$type_name` - The name of the new parameter type.
-/// * `$inner_type` - The inner type of the new parameter type.
+/// * `$inner_type` - The inner type of the new parameter type. This can be any of the primitive
+///   integer types, such as `i64`, `u32`, or `usize`. The parameter converts into an `i64` with
+///   `From` where that's lossless, and with `TryFrom` for `u64`, `u128`, `usize`, `i128`, and
+///   `isize`.
 ///
 /// # Example
 ///
@@ -18,32 +158,80 @@
 /// let param = MyParam(42);
 /// assert_eq!(*param, 42);
 /// assert_eq!(i64::from(param), 42i64);
+///
+/// parameter_type!(SequenceLength, usize);
+///
+/// // A usize may exceed i64::MAX, so its conversion is checked.
+/// let length = SequenceLength(128);
+/// assert_eq!(i64::try_from(length)?, 128i64);
+///
+/// // get() returns the inner value, including in const contexts.
+/// const HEADS: SequenceLength = SequenceLength(4);
+/// let scales = [1.0f32; HEADS.get()];
+/// assert_eq!(scales.len(), 4);
+/// # Ok::<(), tensor_types::TensorTypeError>(())
 /// ```
 /// Copy is also implemented for each newtype (which requires Clone), so that they can be passed by
 /// value. Debug is commonly required, such as by the tch::nn::ModuleT trait. Hash is provided so
 /// that these types can be used as keys in a HashMap. Serialize and Deserialize are provided so
 /// that the model can be saved and loaded.
+///
+/// Display writes the value with thousands separators, as in `1,280`. Disable the default
+/// `num-format` feature to write the plain number instead and drop the `num-format` dependency.
+///
+/// With the `num-traits` feature, Add, Mul, and the `num_traits` Zero and One traits are also
+/// implemented, delegating to the inner type, so that parameters can be used in generic numeric
+/// code, such as summing dimensions.
+///
+/// For dimensions, add `non_negative` to reject negative values when the parameter is created
+/// rather than when a tensor is checked. The inner value is then private, so the parameter is
+/// created with `new()` or `try_from()`, which return a NegativeParameter error for a negative
+/// value. Deserializing checks the value too. DerefMut and FromStr aren't implemented, since they
+/// would bypass the check; parse the inner type and call `new()` instead.
+///
+/// ```
+/// use tensor_types::parameter_type;
+///
+/// parameter_type!(BatchSize, i64, non_negative);
+///
+/// let batch_size = BatchSize::new(32)?;
+/// assert_eq!(*batch_size, 32);
+/// assert!(BatchSize::new(-1).is_err());
+/// # Ok::<(), tensor_types::TensorTypeError>(())
+/// ```
 #[macro_export]
 macro_rules! parameter_type {
-    ($type_name:ident, $inner_type:ty) => {
-        #[derive(
-            Debug, Clone, Copy, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize, Hash,
-        )]
-        pub struct $type_name(pub $inner_type); // TODO: remove pub?
-
-        /// Implements the conversion from an i64 value to the specified parameter type.
-        impl From<i64> for $type_name {
-            fn from(val: i64) -> Self {
-                $type_name(val)
+    // The conversion into an `i64`. It's a TryFrom for the inner types with values out of the range
+    // of `i64`, returning a DimensionOverflow error, and a From for the rest.
+    (@into_i64 $type_name:ident, u64) => { $crate::parameter_type!(@try_into_i64 $type_name); };
+    (@into_i64 $type_name:ident, u128) => { $crate::parameter_type!(@try_into_i64 $type_name); };
+    (@into_i64 $type_name:ident, usize) => { $crate::parameter_type!(@try_into_i64 $type_name); };
+    (@into_i64 $type_name:ident, i128) => { $crate::parameter_type!(@try_into_i64 $type_name); };
+    (@into_i64 $type_name:ident, isize) => { $crate::parameter_type!(@try_into_i64 $type_name); };
+    (@into_i64 $type_name:ident, $inner_type:ident) => {
+        /// Converts a value of type `$type_name` to an `i64`, the type used for tch::Tensor
+        /// dimensions.
+        impl From<$type_name> for i64 {
+            fn from(val: $type_name) -> Self {
+                i64::from(val.0)
             }
         }
+    };
+    (@try_into_i64 $type_name:ident) => {
+        /// Converts a value of type `$type_name` to an `i64`, the type used for tch::Tensor
+        /// dimensions, returning a DimensionOverflow error if it's out of the range of `i64`.
+        impl TryFrom<$type_name> for i64 {
+            type Error = $crate::TensorTypeError;
 
-        /// Converts a value of type `$type_name` to an `i64`.
-        impl From<$type_name> for i64 {
-            fn from(val: $type_name) -> Self {
-                val.0
+            fn try_from(val: $type_name) -> Result<Self, Self::Error> {
+                val.try_to_i64()
             }
         }
+    };
+
+    // The items shared by both forms, which only read the inner value.
+    (@common $type_name:ident, $inner_type:ident) => {
+        $crate::parameter_type!(@into_i64 $type_name, $inner_type);
 
         /// This implementation provides a dereferencing mechanism for the `$type_name` type.
         /// It allows the inner `$inner_type` to be accessed through a reference.
@@ -56,18 +244,12 @@ macro_rules! parameter_type {
             }
         }
 
-        /// Implements the DerefMut trait for the given type.
-        impl std::ops::DerefMut for $type_name {
-            fn deref_mut(&mut self) -> &mut $inner_type {
-                &mut self.0
-            }
-        }
-
         /// Implements the `AsRef` trait for the specified `$type_name` type, allowing it to be
-        /// referenced as an `i64`.
-        impl AsRef<i64> for $type_name {
-            /// Returns a reference to the `i64` value contained within the `$type_name` instance.
-            fn as_ref(&self) -> &i64 {
+        /// referenced as an `$inner_type`.
+        impl AsRef<$inner_type> for $type_name {
+            /// Returns a reference to the `$inner_type` value contained within the `$type_name`
+            /// instance.
+            fn as_ref(&self) -> &$inner_type {
                 &self.0
             }
         }
@@ -75,8 +257,7 @@ macro_rules! parameter_type {
         /// Implements the Display trait for the given type.
         impl std::fmt::Display for $type_name {
             fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
-                use num_format::ToFormattedString;
-                write!(f, "{}", self.0.to_formatted_string(&num_format::Locale::en))
+                $crate::fmt_parameter(&self.0, f)
             }
         }
 
@@ -87,11 +268,129 @@ macro_rules! parameter_type {
             }
         }
 
-        // TODO: Remove.
+        impl $crate::DimensionValue for $type_name {
+            fn to_dimension(self) -> i64 {
+                $crate::DimensionValue::to_dimension(self.0)
+            }
+
+            fn try_to_dimension(self) -> Option<i64> {
+                $crate::DimensionValue::try_to_dimension(self.0)
+            }
+
+            fn to_dimension_saturating(self) -> i64 {
+                $crate::DimensionValue::to_dimension_saturating(self.0)
+            }
+        }
+
+        $crate::__parameter_type_num_traits!($type_name, $inner_type);
+
         impl $type_name {
+            /// Converts the value to an `i64` tensor dimension, returning a DimensionOverflow error
+            /// if it's out of the range of `i64`.
+            pub fn try_to_i64(&self) -> Result<i64, $crate::TensorTypeError> {
+                $crate::DimensionValue::try_to_dimension(self.0).ok_or_else(|| {
+                    $crate::TensorTypeError::DimensionOverflow {
+                        type_name: stringify!($type_name).to_string(),
+                        value: self.0.to_string(),
+                    }
+                })
+            }
+
+            /// Converts the value to an `i64` tensor dimension, clamping it to the range of `i64`.
+            pub fn to_i64_saturating(&self) -> i64 {
+                $crate::DimensionValue::to_dimension_saturating(self.0)
+            }
+
+            /// Returns the inner `$inner_type` value. This is a const fn, so it can be used in
+            /// const contexts, such as the length of an array.
+            pub const fn get(&self) -> $inner_type {
+                self.0
+            }
+
+            // TODO: Remove in favour of get().
             pub fn v(&self) -> $inner_type {
                 self.0
             }
         }
     };
+
+    // The non_negative form keeps the inner value private, so that every value is checked by new().
+    ($type_name:ident, $inner_type:ident, non_negative) => {
+        #[derive(Debug, Clone, Copy, PartialEq, PartialOrd, serde::Serialize, Hash)]
+        pub struct $type_name($inner_type);
+
+        $crate::parameter_type!(@common $type_name, $inner_type);
+
+        impl $type_name {
+            /// Creates the parameter, returning a NegativeParameter error if the value is less
+            /// than zero.
+            pub fn new(val: $inner_type) -> Result<Self, $crate::TensorTypeError> {
+                if val < <$inner_type as Default>::default() {
+                    return Err($crate::TensorTypeError::NegativeParameter {
+                        type_name: stringify!($type_name).to_string(),
+                        value: val.to_string(),
+                    });
+                }
+                Ok($type_name(val))
+            }
+        }
+
+        /// Implements the checked conversion from an `$inner_type` value, which fails for negative
+        /// values.
+        impl TryFrom<$inner_type> for $type_name {
+            type Error = $crate::TensorTypeError;
+
+            fn try_from(val: $inner_type) -> Result<Self, Self::Error> {
+                $type_name::new(val)
+            }
+        }
+
+        /// Deserializes the inner value, then checks it with new().
+        impl<'de> serde::Deserialize<'de> for $type_name {
+            fn deserialize<D: serde::Deserializer<'de>>(deserializer: D) -> Result<Self, D::Error> {
+                let val = <$inner_type as serde::Deserialize>::deserialize(deserializer)?;
+                $type_name::new(val).map_err(serde::de::Error::custom)
+            }
+        }
+    };
+
+    ($type_name:ident, $inner_type:ident) => {
+        #[derive(
+            Debug, Clone, Copy, PartialEq, PartialOrd, serde::Serialize, serde::Deserialize, Hash,
+        )]
+        pub struct $type_name(pub $inner_type); // TODO: remove pub?
+
+        $crate::parameter_type!(@common $type_name, $inner_type);
+
+        /// Implements the conversion from an `$inner_type` value to the specified parameter type.
+        impl From<$inner_type> for $type_name {
+            fn from(val: $inner_type) -> Self {
+                $type_name(val)
+            }
+        }
+
+        /// Implements the DerefMut trait for the given type.
+        impl std::ops::DerefMut for $type_name {
+            fn deref_mut(&mut self) -> &mut $inner_type {
+                &mut self.0
+            }
+        }
+
+        /// Implements FromStr so that parameter values can be parsed from environment variables or
+        /// configuration strings, as in `"128".parse::<$type_name>()`. The value is parsed as an
+        /// `$inner_type`, so negative values are accepted for signed inner types.
+        impl std::str::FromStr for $type_name {
+            type Err = $crate::ParameterParseError;
+
+            fn from_str(s: &str) -> Result<Self, Self::Err> {
+                s.parse::<$inner_type>().map($type_name).map_err(|source| {
+                    $crate::ParameterParseError {
+                        type_name: stringify!($type_name).to_string(),
+                        value: s.to_string(),
+                        source,
+                    }
+                })
+            }
+        }
+    };
 }
@@ -0,0 +1,132 @@
+use serde::de::{DeserializeSeed, Error as _};
+use serde::ser::Error as _;
+use serde::{Deserialize, Deserializer, Serialize, Serializer};
+use std::marker::PhantomData;
+
+use crate::tensor_types::checked_numel;
+use crate::TensorType;
+
+// The kinds that can be serialized. Complex and quantized tensors are not supported.
+const SERIALIZABLE_KINDS: [tch::Kind; 10] = [
+    tch::Kind::Uint8,
+    tch::Kind::Int8,
+    tch::Kind::Int16,
+    tch::Kind::Int,
+    tch::Kind::Int64,
+    tch::Kind::Half,
+    tch::Kind::Float,
+    tch::Kind::Double,
+    tch::Kind::Bool,
+    tch::Kind::BFloat16,
+];
+
+/// The serialized form of a tensor type: the kind and shape of the tensor, and its elements in
+/// row-major order. Floating point tensors store their elements as `f64`, others as `i64`.
+#[derive(Serialize, Deserialize)]
+struct SerializedTensor {
+    kind: String,
+    shape: Vec<i64>,
+    data: TensorData,
+}
+
+#[derive(Serialize, Deserialize)]
+enum TensorData {
+    Float(Vec<f64>),
+    Int(Vec<i64>),
+}
+
+fn kind_name(kind: tch::Kind) -> Option<String> {
+    SERIALIZABLE_KINDS
+        .contains(&kind)
+        .then(|| format!("{kind:?}"))
+}
+
+fn kind_from_name(name: &str) -> Option<tch::Kind> {
+    SERIALIZABLE_KINDS
+        .into_iter()
+        .find(|kind| format!("{kind:?}") == name)
+}
+
+#[doc(hidden)]
+pub fn serialize_tensor<S: Serializer>(
+    tensor: &tch::Tensor,
+    serializer: S,
+) -> Result<S::Ok, S::Error> {
+    let kind = tensor.kind();
+    let name = kind_name(kind)
+        .ok_or_else(|| S::Error::custom(format!("can't serialize a tensor of kind {kind:?}")))?;
+    let flat = tensor.to_device(tch::Device::Cpu).flatten(0, -1);
+    let data = if tensor.is_floating_point() {
+        TensorData::Float(Vec::try_from(flat.to_kind(tch::Kind::Double)).map_err(S::Error::custom)?)
+    } else {
+        TensorData::Int(Vec::try_from(flat.to_kind(tch::Kind::Int64)).map_err(S::Error::custom)?)
+    };
+    SerializedTensor {
+        kind: name,
+        shape: tensor.size(),
+        data,
+    }
+    .serialize(serializer)
+}
+
+#[doc(hidden)]
+pub fn deserialize_tensor<'de, D: Deserializer<'de>>(
+    deserializer: D,
+) -> Result<tch::Tensor, D::Error> {
+    let serialized = SerializedTensor::deserialize(deserializer)?;
+    let kind = kind_from_name(&serialized.kind)
+        .ok_or_else(|| D::Error::custom(format!("unknown tensor kind {:?}", serialized.kind)))?;
+    // The shape comes from the input, so it's checked before it's used to build the tensor.
+    if let Some(dim) = serialized.shape.iter().find(|&&dim| dim < 0) {
+        return Err(D::Error::custom(format!(
+            "negative dimension {dim} in the tensor shape {:?}",
+            serialized.shape
+        )));
+    }
+    let numel = checked_numel("serialized tensor", &serialized.shape).map_err(D::Error::custom)?;
+    let len = match &serialized.data {
+        TensorData::Float(data) => data.len(),
+        TensorData::Int(data) => data.len(),
+    };
+    if i64::try_from(len) != Ok(numel) {
+        return Err(D::Error::custom(format!(
+            "tensor data has {len} elements, but the shape {:?} requires {numel}",
+            serialized.shape
+        )));
+    }
+    let tensor = match serialized.data {
+        TensorData::Float(data) => tch::Tensor::from_slice(&data),
+        TensorData::Int(data) => tch::Tensor::from_slice(&data),
+    };
+    Ok(tensor.reshape(serialized.shape).to_kind(kind))
+}
+
+/// A `DeserializeSeed` that deserializes a tensor type and checks it against the parameters, as
+/// `new()` does. Create one with `TensorType::seed()`.
+///
+/// Tensor types also implement `Deserialize` directly so that they can be fields of structs that
+/// derive it. **That form doesn't check the tensor**: the parameters aren't available, so the
+/// value is wrapped with `new_unchecked()` and may have any shape or kind. Call `verify()` on the
+/// result before relying on the type.
+pub struct TensorSeed<'a, T: TensorType> {
+    params: &'a T::InnerType,
+    marker: PhantomData<T>,
+}
+
+impl<'a, T: TensorType> TensorSeed<'a, T> {
+    pub fn new(params: &'a T::InnerType) -> Self {
+        Self {
+            params,
+            marker: PhantomData,
+        }
+    }
+}
+
+impl<'de, T: TensorType> DeserializeSeed<'de> for TensorSeed<'_, T> {
+    type Value = T;
+
+    fn deserialize<D: Deserializer<'de>>(self, deserializer: D) -> Result<T, D::Error> {
+        let tensor = deserialize_tensor(deserializer)?;
+        T::new(tensor, self.params).map_err(D::Error::custom)
+    }
+}
@@ -1,32 +1,1912 @@
+/// The TensorType trait is implemented by every type created with the `tensor_type!` macro. It
+/// allows generic functions to be written over tensor types, for example to accept any tensor type
+/// checked against the same parameters structure.
+///
+/// # Example
+/// ```
+/// use tensor_types::{tensor_type, TensorType, TensorTypeError};
+/// use tch::{Device, Kind, Tensor};
+///
+/// pub struct Params {
+///     batch_size: i64,
+///     sequence_length: i64,
+/// }
+/// tensor_type!(BatchSeqTensor, [batch_size, sequence_length], Params, Kind::Float);
+///
+/// fn attention<T: TensorType<InnerType = Params>>(
+///     query: &T,
+///     params: &Params,
+/// ) -> Result<T, TensorTypeError> {
+///     query.apply_fn(|t| t.triu(1), params)
+/// }
+///
+/// let params = Params { batch_size: 2, sequence_length: 3 };
+/// let t = Tensor::randn([2, 3], (Kind::Float, Device::Cpu));
+/// let query = BatchSeqTensor::new(t, &params)?;
+/// let _ = attention(&query, &params)?;
+/// # Ok::<(), TensorTypeError>(())
+/// ```
 pub trait TensorType {
-    type InnerType;
+    /// The parameters type whose fields give the runtime dimensions of the tensor. For types
+    /// declared with `dynamic`, it's the slice of dimensions, `[i64]`.
+    type InnerType: ?Sized;
+
+    /// The name of the type, as reported in errors.
+    const NAME: &'static str;
+
+    /// The kinds accepted by the type. This is empty for types whose kinds are given by a field of
+    /// the parameters.
+    const KINDS: &'static [tch::Kind];
+
+    /// The options given to the tensor_type!() macro for this type.
+    const OPTIONS: crate::TensorTypeOptions;
+
+    /// Wrap a tensor, checking it against the dimensions given by `params` and the declared kind.
     fn new(tensor: tch::Tensor, params: &Self::InnerType) -> Result<Self, crate::TensorTypeError>
     where
         Self: Sized;
+
+    /// Check a borrowed tensor as `new()` does, returning a wrapper that borrows it rather than
+    /// taking ownership. This gives a typed, read-only view of a tensor owned elsewhere, such as a
+    /// field of a model, without a shallow clone.
+    fn new_ref<'a>(
+        tensor: &'a tch::Tensor,
+        params: &Self::InnerType,
+    ) -> Result<crate::TensorRef<'a, Self>, crate::TensorTypeError>
+    where
+        Self: Sized;
+
+    /// Re-check the wrapped tensor against the dimensions given by `params` and the declared kind,
+    /// for example after modifying it in place through `tensor_mut()` with the `Warn` action, or
+    /// through the field, if declared `pub` with `#[public_tensor]`.
+    fn verify(&self, params: &Self::InnerType) -> Result<(), crate::TensorTypeError>;
+
+    /// Check the shape of a tensor against this type without wrapping it, returning the same
+    /// ShapeMismatch or RankMismatch error as `new()`. The kind isn't checked.
+    fn check_shape(
+        tensor: &tch::Tensor,
+        params: &Self::InnerType,
+    ) -> Result<(), crate::TensorTypeError>;
+
+    /// Wrap a tensor without checking its shape or kind. This is an escape hatch for hot paths
+    /// where the caller has already verified the tensor, for example once at an API boundary.
+    fn new_unchecked(tensor: tch::Tensor) -> Self
+    where
+        Self: Sized;
+
+    /// Return a reference to the wrapped tensor.
     fn tensor(&self) -> &tch::Tensor;
-    fn tensor_mut(&mut self) -> &mut tch::Tensor;
+
+    /// Return a guard giving mutable access to the wrapped tensor. When the guard is dropped, the
+    /// tensor is re-checked against `params`, and the `on_invalid_mut` option decides what happens
+    /// if it no longer matches the type.
+    fn tensor_mut<'a>(
+        &'a mut self,
+        params: &'a Self::InnerType,
+    ) -> crate::TensorMut<'a, Self::InnerType>;
+
+    /// Apply a function to the wrapped tensor, checking that the result is still of this type.
     fn apply_fn<F>(&self, tfn: F, params: &Self::InnerType) -> Result<Self, crate::TensorTypeError>
     where
         F: FnOnce(&tch::Tensor) -> tch::Tensor,
         Self: Sized;
+
+    /// Create a new wrapper around a shallow clone of the wrapped tensor, re-checking it against
+    /// `params`. Use `shallow_clone()` when re-validation isn't needed.
     fn clone(&self, params: &Self::InnerType) -> Result<Self, crate::TensorTypeError>
     where
         Self: Sized;
+
+    /// Create a new wrapper around a shallow clone of the wrapped tensor without checking it. A
+    /// shallow clone can't change the shape or kind, so no params are needed. As with
+    /// `tch::Tensor::shallow_clone`, the clone shares its data with the original, so in-place
+    /// changes to one are visible in the other. Use `to_tensor()` for a deep copy.
+    fn shallow_clone(&self) -> Self
+    where
+        Self: Sized,
+    {
+        Self::new_unchecked(self.tensor().shallow_clone())
+    }
+
+    /// Unwrap the underlying tch::Tensor.
     fn into_inner(self) -> tch::Tensor;
+
+    /// Unwrap the underlying tch::Tensor after re-checking it against `params`, making the same
+    /// checks as `new()`. Use this at API boundaries where the tensor is handed to other code, for
+    /// example after modifying it in place.
+    fn into_inner_checked(
+        self,
+        params: &Self::InnerType,
+    ) -> Result<tch::Tensor, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        self.verify(params)?;
+        Ok(self.into_inner())
+    }
+
+    /// Return a deep copy of the wrapped tensor, leaving the wrapper in place. Unlike `tensor()`,
+    /// which borrows the tensor, and `into_inner()`, which moves it, the returned tensor owns
+    /// separate data, so modifying it doesn't affect the wrapper.
+    fn to_tensor(&self) -> tch::Tensor {
+        self.tensor().copy()
+    }
+
+    /// Return the kind used when creating tensors of this type: the first declared kind, or the
+    /// kind given by the parameters. An EmptyKindSet error is returned if the parameters give an
+    /// empty set of kinds.
+    fn creation_kind(params: &Self::InnerType) -> Result<tch::Kind, crate::TensorTypeError>;
+
+    /// Return the dimensions a tensor of this type must have, given the parameters. Types that
+    /// only check the rank of their tensors return an UnknownDimensions error, and an
+    /// InvalidDimension error is returned if a dimension is less than one.
+    ///
+    /// # Example
+    /// ```
+    /// use tensor_types::{tensor_type, TensorType};
+    /// use tch::{Device, Kind, Tensor};
+    ///
+    /// pub struct Params {
+    ///     rows: i64,
+    ///     cols: i64,
+    /// }
+    /// tensor_type!(Matrix, [rows, cols], Params, Kind::Float);
+    ///
+    /// let params = Params { rows: 2, cols: 3 };
+    /// assert_eq!(Matrix::expected_size(&params)?, vec![2, 3]);
+    ///
+    /// let t = Tensor::ones(Matrix::expected_size(&params)?, (Kind::Float, Device::Cpu));
+    /// let m = Matrix::new(t, &params)?;
+    /// # Ok::<(), tensor_types::TensorTypeError>(())
+    /// ```
+    fn expected_size(params: &Self::InnerType) -> Result<Vec<i64>, crate::TensorTypeError>;
+
+    /// Return the number of elements a tensor of this type must have, given the parameters: the
+    /// product of its expected dimensions. This allows buffers to be sized before a tensor exists.
+    /// Errors are returned as by `expected_size()`, and a DimensionOverflow error is returned if
+    /// the product is out of the range of `i64`.
+    fn expected_numel(params: &Self::InnerType) -> Result<i64, crate::TensorTypeError> {
+        crate::tensor_types::checked_numel(Self::NAME, &Self::expected_size(params)?)
+    }
+
+    /// Compare the size of a tensor with the expected size, returning `(axis, expected, found)`
+    /// for each axis at which they differ, for tools that report mismatches in their own format.
+    /// If the ranks differ, an axis that's absent from one of the sizes is reported with size 0
+    /// on that side. The sizes are compared in full, without the broadcast option. Errors are
+    /// returned as by `expected_size()`.
+    fn shape_diff(
+        tensor: &tch::Tensor,
+        params: &Self::InnerType,
+    ) -> Result<Vec<(usize, i64, i64)>, crate::TensorTypeError> {
+        let expected = Self::expected_size(params)?;
+        let found = tensor.size();
+        Ok((0..expected.len().max(found.len()))
+            .map(|axis| {
+                let size_of = |size: &[i64]| size.get(axis).copied().unwrap_or(0);
+                (axis, size_of(&expected), size_of(&found))
+            })
+            .filter(|(_, expected, found)| expected != found)
+            .collect())
+    }
+
+    /// Re-tag the value as another tensor type with the same parameters, for example to convert a
+    /// general `BatchSeqDModel` into a domain-specific `AttentionQuery`. The new value wraps a
+    /// shallow clone of the tensor, which is checked against the target type's shape and kind.
+    fn convert<U>(&self, params: &Self::InnerType) -> Result<U, crate::TensorTypeError>
+    where
+        U: TensorType<InnerType = Self::InnerType>,
+        Self: Sized,
+    {
+        U::new(self.tensor().shallow_clone(), params)
+    }
+
+    /// Cast the wrapped tensor to `kind`, wrapping the result in another tensor type with the same
+    /// parameters, for example to convert a `FloatTensor` into a `DoubleTensor`. The result is
+    /// checked against the target type's shape and kind, so a KindMismatch error is returned if
+    /// `kind` isn't accepted by the target type.
+    fn to_kind_as<U>(
+        &self,
+        kind: tch::Kind,
+        params: &Self::InnerType,
+    ) -> Result<U, crate::TensorTypeError>
+    where
+        U: TensorType<InnerType = Self::InnerType>,
+        Self: Sized,
+    {
+        let mut converted = U::new(self.tensor().to_kind(kind), params)?;
+        converted.extend_kind_history(self.kind_history());
+        Ok(converted)
+    }
+
+    /// Apply a function to the wrapped tensor, wrapping the result in another tensor type. Use this
+    /// instead of `apply_fn()` when the function deliberately changes the shape or kind. The
+    /// result is checked against the target type's shape and kind.
+    fn apply_into<U, F>(&self, tfn: F, params: &U::InnerType) -> Result<U, crate::TensorTypeError>
+    where
+        U: TensorType,
+        F: FnOnce(&tch::Tensor) -> tch::Tensor,
+        Self: Sized,
+    {
+        U::new(tfn(self.tensor()), params)
+    }
+
+    /// Apply a binary function to the wrapped tensors of this value and another of the same type,
+    /// such as a residual add, wrapping the result in this type. The result is checked like
+    /// `new()`, so a function that changes the shape or kind returns an error.
+    fn apply2<F>(
+        &self,
+        other: &Self,
+        tfn: F,
+        params: &Self::InnerType,
+    ) -> Result<Self, crate::TensorTypeError>
+    where
+        F: FnOnce(&tch::Tensor, &tch::Tensor) -> tch::Tensor,
+        Self: Sized,
+    {
+        Self::new(tfn(self.tensor(), other.tensor()), params)
+    }
+
+    /// Add another value of the same type element-wise, checking the result like `new()`. Unlike
+    /// the `+` operator, which is only implemented for the fixed `[fields]` forms, this is
+    /// available for every form, and a Tch error is returned if the shapes don't broadcast.
+    fn checked_add(
+        &self,
+        other: &Self,
+        params: &Self::InnerType,
+    ) -> Result<Self, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        let sum = crate::tensor_types::tch_result(Self::NAME, self.tensor().f_add(other.tensor()))?;
+        Self::new(sum, params)
+    }
+
+    /// Subtract another value of the same type element-wise, as for `checked_add()`.
+    fn checked_sub(
+        &self,
+        other: &Self,
+        params: &Self::InnerType,
+    ) -> Result<Self, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        let difference =
+            crate::tensor_types::tch_result(Self::NAME, self.tensor().f_sub(other.tensor()))?;
+        Self::new(difference, params)
+    }
+
+    /// Multiply by another value of the same type element-wise, as for `checked_add()`.
+    fn checked_mul(
+        &self,
+        other: &Self,
+        params: &Self::InnerType,
+    ) -> Result<Self, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        let product =
+            crate::tensor_types::tch_result(Self::NAME, self.tensor().f_mul(other.tensor()))?;
+        Self::new(product, params)
+    }
+
+    /// Divide by another value of the same type element-wise, as for `checked_add()`. Integer
+    /// tensors are divided with truncation, as by the `/` operator, and a Tch error is returned
+    /// where torch reports a division by zero rather than panicking.
+    fn checked_div(
+        &self,
+        other: &Self,
+        params: &Self::InnerType,
+    ) -> Result<Self, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        let quotient = if self.tensor().is_floating_point() {
+            self.tensor().f_div(other.tensor())
+        } else {
+            self.tensor().f_divide_tensor_mode(other.tensor(), "trunc")
+        };
+        Self::new(
+            crate::tensor_types::tch_result(Self::NAME, quotient)?,
+            params,
+        )
+    }
+
+    /// Reshape the wrapped tensor into the shape of another tensor type, returning an instance of
+    /// that type. A NumelMismatch error is returned if the number of elements differs, before the
+    /// reshape is attempted.
+    ///
+    /// # Example
+    /// ```
+    /// use tensor_types::{tensor_type, TensorType};
+    /// use tch::{Device, Kind, Tensor};
+    ///
+    /// pub struct Params {
+    ///     batch_size: i64,
+    ///     sequence_length: i64,
+    ///     d_model: i64,
+    /// }
+    /// tensor_type!(BatchSeqDModel, [batch_size, sequence_length, d_model], Params, Kind::Float);
+    /// tensor_type!(BatchSeqTimesDModel, [batch_size, sequence_length * d_model], Params, Kind::Float);
+    ///
+    /// let params = Params { batch_size: 2, sequence_length: 3, d_model: 4 };
+    /// let t = Tensor::randn([2, 3, 4], (Kind::Float, Device::Cpu));
+    /// let x = BatchSeqDModel::new(t, &params)?;
+    /// let flat: BatchSeqTimesDModel = x.reshape_to(&params)?;
+    /// assert_eq!(flat.size(), &[2, 12]);
+    /// # Ok::<(), tensor_types::TensorTypeError>(())
+    /// ```
+    fn reshape_to<U: TensorType>(&self, params: &U::InnerType) -> Result<U, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        let target_size = U::expected_size(params)?;
+        let expected = crate::tensor_types::checked_numel(U::NAME, &target_size)?;
+        let found = self.tensor().numel() as i64;
+        if expected != found {
+            return Err(crate::TensorTypeError::NumelMismatch {
+                type_name: U::NAME.to_string(),
+                expected,
+                found,
+            });
+        }
+        U::new(self.tensor().reshape(target_size), params)
+    }
+
+    /// Insert a dimension of size one at `dim`, returning an instance of the target tensor type.
+    /// The result is checked against the target type's shape and kind. A Tch error is returned if
+    /// `dim` is out of range.
+    fn unsqueeze_to<U: TensorType>(
+        &self,
+        dim: i64,
+        params: &U::InnerType,
+    ) -> Result<U, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        let unsqueezed =
+            crate::tensor_types::tch_result(Self::NAME, self.tensor().f_unsqueeze(dim))?;
+        U::new(unsqueezed, params)
+    }
+
+    /// Remove the dimension of size one at `dim`, returning an instance of the target tensor type.
+    /// The result is checked against the target type's shape and kind. A Tch error is returned if
+    /// `dim` is out of range.
+    fn squeeze_to<U: TensorType>(
+        &self,
+        dim: i64,
+        params: &U::InnerType,
+    ) -> Result<U, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        let squeezed =
+            crate::tensor_types::tch_result(Self::NAME, self.tensor().f_squeeze_dim(dim))?;
+        U::new(squeezed, params)
+    }
+
+    /// Take the mean along `dim`, removing it, and return an instance of the lower-rank target
+    /// tensor type, e.g. `mean_to(1, params)` to pool a `[batch_size, seq_len, d_model]` tensor into
+    /// `[batch_size, d_model]`. The result is checked against the target type's shape and kind.
+    /// A Tch error is returned if `dim` is out of range, or if the tensor isn't of a floating
+    /// point kind.
+    fn mean_to<U: TensorType>(
+        &self,
+        dim: i64,
+        params: &U::InnerType,
+    ) -> Result<U, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        let mean = self
+            .tensor()
+            .f_mean_dim(&[dim][..], false, None::<tch::Kind>);
+        U::new(crate::tensor_types::tch_result(Self::NAME, mean)?, params)
+    }
+
+    /// Take the sum along `dim`, removing it, and return an instance of the lower-rank target
+    /// tensor type. As with tch::Tensor::sum_dim_intlist, integer tensors are summed as Int64. The
+    /// result is checked against the target type's shape and kind. A Tch error is returned if
+    /// `dim` is out of range.
+    fn sum_to<U: TensorType>(
+        &self,
+        dim: i64,
+        params: &U::InnerType,
+    ) -> Result<U, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        let sum = self
+            .tensor()
+            .f_sum_dim_intlist(&[dim][..], false, None::<tch::Kind>);
+        U::new(crate::tensor_types::tch_result(Self::NAME, sum)?, params)
+    }
+
+    /// Swap the dimensions `dim0` and `dim1`, returning an instance of the target tensor type. A
+    /// ShapeMismatch error is returned if the transposed shape doesn't match the target type, and
+    /// a Tch error if either dimension is out of range.
+    fn transpose_to<U: TensorType>(
+        &self,
+        dim0: i64,
+        dim1: i64,
+        params: &U::InnerType,
+    ) -> Result<U, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        let transposed =
+            crate::tensor_types::tch_result(Self::NAME, self.tensor().f_transpose(dim0, dim1))?;
+        U::new(transposed, params)
+    }
+
+    /// Flatten the dimensions from `start_dim` to `end_dim` inclusive into one, returning an
+    /// instance of the target tensor type, e.g. `flatten_to(1, 2, params)` to flatten a
+    /// `[batch_size, seq_len, d_model]` tensor into `[batch_size, seq_len * d_model]`. The number
+    /// of elements is unchanged, so a ShapeMismatch error from the target type shows that the
+    /// wrong axes were flattened. A Tch error is returned if either dimension is out of range, or
+    /// if `start_dim` comes after `end_dim`.
+    fn flatten_to<U: TensorType>(
+        &self,
+        start_dim: i64,
+        end_dim: i64,
+        params: &U::InnerType,
+    ) -> Result<U, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        let flattened = self.tensor().f_flatten(start_dim, end_dim);
+        U::new(
+            crate::tensor_types::tch_result(Self::NAME, flattened)?,
+            params,
+        )
+    }
+
+    /// Broadcast the wrapped tensor to the expected size of the target tensor type, returning an
+    /// instance of that type, e.g. to expand a `[1, 1, d_model]` bias to
+    /// `[batch_size, seq_len, d_model]`. The result shares its data with this value. A
+    /// ShapeMismatch error is returned if the tensor can't be broadcast to the target size.
+    fn expand_to<U: TensorType>(&self, params: &U::InnerType) -> Result<U, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        let target_size = U::expected_size(params)?;
+        match self.tensor().f_expand(&target_size, false) {
+            Ok(expanded) => U::new(expanded, params),
+            Err(_) => Err(crate::TensorTypeError::ShapeMismatch {
+                type_name: U::NAME.to_string(),
+                dim_names: vec![],
+                expected: target_size,
+                found: self.tensor().size(),
+            }),
+        }
+    }
+
+    /// Reorder the dimensions as given by `dims`, returning an instance of the target tensor type,
+    /// e.g. `permute_to(&[0, 2, 1, 3], params)` to swap the head and sequence axes of attention
+    /// scores. A RankMismatch error is returned if `dims` doesn't give each dimension, a Tch error
+    /// if a dimension is repeated or out of range, and a ShapeMismatch error if the permuted shape
+    /// doesn't match the target type.
+    fn permute_to<U: TensorType>(
+        &self,
+        dims: &[i64],
+        params: &U::InnerType,
+    ) -> Result<U, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        if dims.len() != self.tensor().dim() {
+            return Err(crate::TensorTypeError::RankMismatch {
+                type_name: Self::NAME.to_string(),
+                expected_rank: self.tensor().dim(),
+                found_rank: dims.len(),
+            });
+        }
+        let permuted = crate::tensor_types::tch_result(Self::NAME, self.tensor().f_permute(dims))?;
+        U::new(permuted, params)
+    }
+
+    /// Concatenate values of this type along `dim`, checking that the result is still of this
+    /// type. Concatenating changes the size of `dim`, so this is useful for types that don't fix
+    /// the size of that axis, such as those declared with `rank = N`. An EmptyItems error is
+    /// returned if `items` is empty, and a Tch error if the tensors can't be concatenated, for
+    /// example if `dim` is out of range.
+    fn cat(
+        items: &[Self],
+        dim: i64,
+        params: &Self::InnerType,
+    ) -> Result<Self, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        if items.is_empty() {
+            return Err(crate::TensorTypeError::EmptyItems {
+                type_name: Self::NAME.to_string(),
+            });
+        }
+        let tensors: Vec<&tch::Tensor> = items.iter().map(|item| item.tensor()).collect();
+        match tch::Tensor::f_cat(&tensors, dim) {
+            Ok(joined) => Self::new(joined, params),
+            Err(source) => Err(crate::TensorTypeError::Tch {
+                type_name: Self::NAME.to_string(),
+                source,
+            }),
+        }
+    }
+
+    /// Concatenate this value and a value of another tensor type along `dim`, returning an
+    /// instance of the output tensor type, such as `[b, s, d1]` and `[b, s, d2]` into `[b, s, d1 +
+    /// d2]`. A RankMismatch error is returned if the ranks differ, an AxisMismatch error if any
+    /// other axis differs, and a Tch error if `dim` is out of range. The result is checked against
+    /// the output type's shape and kind.
+    fn concat_channels<Rhs: TensorType, Out: TensorType>(
+        &self,
+        rhs: &Rhs,
+        dim: i64,
+        params: &Out::InnerType,
+    ) -> Result<Out, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        let rank = self.tensor().dim();
+        if rhs.tensor().dim() != rank {
+            return Err(crate::TensorTypeError::RankMismatch {
+                type_name: Rhs::NAME.to_string(),
+                expected_rank: rank,
+                found_rank: rhs.tensor().dim(),
+            });
+        }
+        let axis = if dim < 0 { dim + rank as i64 } else { dim };
+        for other_axis in (0..rank).filter(|&other_axis| other_axis as i64 != axis) {
+            self.assert_same_axis(rhs, other_axis, other_axis)?;
+        }
+        let joined = tch::Tensor::f_cat(&[self.tensor(), rhs.tensor()], dim);
+        Out::new(crate::tensor_types::tch_result(Self::NAME, joined)?, params)
+    }
+
+    /// Stack values of this type along a new dimension `dim`, returning an instance of the
+    /// higher-rank target tensor type. An EmptyItems error is returned if `items` is empty, a
+    /// ShapeMismatch error if the items have differing shapes, and a Tch error if they can't be
+    /// stacked, for example if `dim` is out of range.
+    fn stack<U: TensorType>(
+        items: &[Self],
+        dim: i64,
+        params: &U::InnerType,
+    ) -> Result<U, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        let tensors: Vec<&tch::Tensor> = items.iter().map(|item| item.tensor()).collect();
+        let Some(first) = tensors.first() else {
+            return Err(crate::TensorTypeError::EmptyItems {
+                type_name: Self::NAME.to_string(),
+            });
+        };
+        let expected = first.size();
+        for tensor in &tensors[1..] {
+            let found = tensor.size();
+            if found != expected {
+                return Err(crate::TensorTypeError::ShapeMismatch {
+                    type_name: Self::NAME.to_string(),
+                    dim_names: vec![],
+                    expected,
+                    found,
+                });
+            }
+        }
+        match tch::Tensor::f_stack(&tensors, dim) {
+            Ok(stacked) => U::new(stacked, params),
+            Err(source) => Err(crate::TensorTypeError::Tch {
+                type_name: Self::NAME.to_string(),
+                source,
+            }),
+        }
+    }
+
+    /// Split the wrapped tensor into `chunks` equal parts along `dim`, returning each part as an
+    /// instance of the target tensor type. An InvalidAxis error is returned if `dim` is out of
+    /// range, and an IndivisibleDimension error if the size of `dim` isn't a multiple of `chunks`.
+    fn chunk_to<U: TensorType>(
+        &self,
+        chunks: i64,
+        dim: i64,
+        params: &U::InnerType,
+    ) -> Result<Vec<U>, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        let size = self.tensor().size();
+        let axis = if dim < 0 {
+            dim + size.len() as i64
+        } else {
+            dim
+        };
+        let Some(&dim_size) = usize::try_from(axis).ok().and_then(|axis| size.get(axis)) else {
+            return Err(crate::TensorTypeError::InvalidAxis {
+                type_name: Self::NAME.to_string(),
+                axis: dim,
+                rank: size.len(),
+            });
+        };
+        if chunks < 1 || dim_size % chunks != 0 {
+            return Err(crate::TensorTypeError::IndivisibleDimension {
+                type_name: Self::NAME.to_string(),
+                axis: axis as usize,
+                size: dim_size,
+                chunks,
+            });
+        }
+        self.tensor()
+            .chunk(chunks, dim)
+            .into_iter()
+            .map(|chunk| U::new(chunk, params))
+            .collect()
+    }
+
+    /// Iterate over the leading dimension of the wrapped tensor, such as the samples of a batch,
+    /// yielding each slice as a tch::Tensor with that dimension removed. The slices share their
+    /// data with the wrapped tensor. A Tch error is returned if the tensor is a scalar, which has
+    /// no leading dimension.
+    fn iter_dim0(&self) -> Result<impl Iterator<Item = tch::Tensor>, crate::TensorTypeError> {
+        let slices = crate::tensor_types::tch_result(Self::NAME, self.tensor().f_unbind(0))?;
+        Ok(slices.into_iter())
+    }
+
+    /// Iterate over the leading dimension of the wrapped tensor as `iter_dim0()` does, wrapping
+    /// each slice in the target tensor type. Each slice is checked against the target type's shape
+    /// and kind, so the iterator yields Results. For a scalar, it yields only the Tch error
+    /// returned by `iter_dim0()`.
+    fn iter_dim0_as<'a, U: TensorType + 'a>(
+        &self,
+        params: &'a U::InnerType,
+    ) -> impl Iterator<Item = Result<U, crate::TensorTypeError>> + 'a {
+        let (slices, error) =
+            match crate::tensor_types::tch_result(Self::NAME, self.tensor().f_unbind(0)) {
+                Ok(slices) => (slices, None),
+                Err(error) => (vec![], Some(error)),
+            };
+        slices
+            .into_iter()
+            .map(move |slice| U::new(slice, params))
+            .chain(error.map(Err))
+    }
+
+    /// Apply a function to each slice along the leading dimension, such as each sample of a batch,
+    /// and stack the results back into a value of this type. This is like `apply_fn()`, but for
+    /// operations that must run sample by sample. A ShapeMismatch error is returned if the function
+    /// returns slices of differing shapes, and the stacked result is checked like `new()`. An
+    /// empty leading dimension has no slices to apply the function to, so the value is re-checked
+    /// and returned as it is. A Tch error is returned if the tensor is a scalar.
+    fn map_batch<F>(&self, f: F, params: &Self::InnerType) -> Result<Self, crate::TensorTypeError>
+    where
+        F: Fn(&tch::Tensor) -> tch::Tensor,
+        Self: Sized,
+    {
+        let slices: Vec<tch::Tensor> = self.iter_dim0()?.map(|slice| f(&slice)).collect();
+        let Some(first) = slices.first() else {
+            return Self::new(self.tensor().shallow_clone(), params);
+        };
+        let expected = first.size();
+        for slice in &slices[1..] {
+            let found = slice.size();
+            if found != expected {
+                return Err(crate::TensorTypeError::ShapeMismatch {
+                    type_name: Self::NAME.to_string(),
+                    dim_names: vec![],
+                    expected,
+                    found,
+                });
+            }
+        }
+        Self::new(tch::Tensor::stack(&slices, 0), params)
+    }
+
+    /// Check that the size of `self_axis` of this value equals the size of `other_axis` of a value
+    /// of another tensor type, such as the shared sequence axis of a query and a key. An
+    /// AxisMismatch error is returned if they differ, and an InvalidAxis error, naming the type
+    /// whose tensor lacks the axis, if either axis is out of range.
+    fn assert_same_axis<U: TensorType>(
+        &self,
+        other: &U,
+        self_axis: usize,
+        other_axis: usize,
+    ) -> Result<(), crate::TensorTypeError> {
+        let found = crate::tensor_types::axis_size(Self::NAME, self.tensor(), self_axis)?;
+        let expected = crate::tensor_types::axis_size(U::NAME, other.tensor(), other_axis)?;
+        if found != expected {
+            return Err(crate::TensorTypeError::AxisMismatch {
+                type_name: Self::NAME.to_string(),
+                axis: self_axis,
+                expected,
+                found,
+            });
+        }
+        Ok(())
+    }
+
+    /// Check that the wrapped tensor is on the same device as that of a value of another tensor
+    /// type, so that an operation combining them can't fail inside libtorch. A DeviceMismatch
+    /// error is returned if they differ.
+    fn assert_same_device<U: TensorType>(&self, other: &U) -> Result<(), crate::TensorTypeError> {
+        let found = self.tensor().device();
+        let expected = other.tensor().device();
+        if found != expected {
+            return Err(crate::TensorTypeError::DeviceMismatch {
+                type_name: Self::NAME.to_string(),
+                expected,
+                found,
+            });
+        }
+        Ok(())
+    }
+
+    /// Apply softmax along `dim`, returning a value of the same type. The result keeps the kind of
+    /// the wrapped tensor, and is checked as by `apply_fn()`.
+    fn softmax(&self, dim: i64, params: &Self::InnerType) -> Result<Self, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        self.apply_fn(|t| t.softmax(dim, t.kind()), params)
+    }
+
+    /// Apply log-softmax along `dim`, returning a value of the same type. The result keeps the
+    /// kind of the wrapped tensor, and is checked as by `apply_fn()`.
+    fn log_softmax(
+        &self,
+        dim: i64,
+        params: &Self::InnerType,
+    ) -> Result<Self, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        self.apply_fn(|t| t.log_softmax(dim, t.kind()), params)
+    }
+
+    /// Apply the rectified linear unit, returning a value of the same type. The result is checked
+    /// as by `apply_fn()`.
+    fn relu(&self, params: &Self::InnerType) -> Result<Self, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        self.apply_fn(|t| t.relu(), params)
+    }
+
+    /// Apply the Gaussian error linear unit, computed exactly rather than by the tanh
+    /// approximation, returning a value of the same type. The result is checked as by
+    /// `apply_fn()`.
+    fn gelu(&self, params: &Self::InnerType) -> Result<Self, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        self.apply_fn(|t| t.gelu("none"), params)
+    }
+
+    /// Apply the logistic sigmoid, returning a value of the same type. The result is checked as by
+    /// `apply_fn()`.
+    fn sigmoid(&self, params: &Self::InnerType) -> Result<Self, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        self.apply_fn(|t| t.sigmoid(), params)
+    }
+
+    /// Clamp each element to the range `min..=max`, returning a value of the same type. The result
+    /// is checked as by `apply_fn()`.
+    fn clamp(
+        &self,
+        min: f64,
+        max: f64,
+        params: &Self::InnerType,
+    ) -> Result<Self, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        self.apply_fn(|t| t.clamp(min, max), params)
+    }
+
+    /// Normalize the last dimension to zero mean and unit variance, as by layer normalization
+    /// without a learned weight and bias, returning a value of the same type. `eps` is added to
+    /// the variance for numerical stability. The result is checked as by `apply_fn()`.
+    fn layer_norm(&self, eps: f64, params: &Self::InnerType) -> Result<Self, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        self.apply_fn(
+            |t| {
+                let last = t.size().last().copied().unwrap_or(1);
+                t.layer_norm([last], None::<tch::Tensor>, None::<tch::Tensor>, eps, false)
+            },
+            params,
+        )
+    }
+
+    /// Multiply the wrapped tensor by the tensor of another type, as by `tch::Tensor::matmul`,
+    /// returning an instance of the output tensor type, such as `[b, m, k] @ [b, k, n] -> [b, m,
+    /// n]`. An AxisMismatch error is returned if the contracted dimensions differ, and a Tch error
+    /// if either tensor is a scalar or the batch dimensions don't broadcast. The result is checked
+    /// against the output type's shape and kind.
+    fn matmul<Rhs: TensorType, Out: TensorType>(
+        &self,
+        rhs: &Rhs,
+        params: &Out::InnerType,
+    ) -> Result<Out, crate::TensorTypeError>
+    where
+        Self: Sized,
+    {
+        let (self_dim, rhs_dim) = (self.tensor().dim(), rhs.tensor().dim());
+        if self_dim > 0 && rhs_dim > 0 {
+            let rhs_axis = if rhs_dim == 1 { 0 } else { rhs_dim - 2 };
+            self.assert_same_axis(rhs, self_dim - 1, rhs_axis)?;
+        }
+        let product = self.tensor().f_matmul(rhs.tensor());
+        Out::new(
+            crate::tensor_types::tch_result(Self::NAME, product)?,
+            params,
+        )
+    }
+
+    /// Return true if the wrapped tensors have the same kind, device, and shape and all of their
+    /// elements are within `tol` of each other. Prefer this to `==` for floating point tensors.
+    /// Tensors of different kinds or on different devices aren't compared, and return false.
+    fn approx_eq(&self, other: &Self, tol: f64) -> bool
+    where
+        Self: Sized,
+    {
+        let (tensor, other) = (self.tensor(), other.tensor());
+        tensor.kind() == other.kind()
+            && tensor.device() == other.device()
+            && tensor.size() == other.size()
+            && tensor.f_allclose(other, 0.0, tol, false).unwrap_or(false)
+    }
+
+    /// Return the elements of the wrapped tensor in row-major order as `f64`s, converting them
+    /// from the tensor's kind. The tensor is copied to the CPU if necessary.
+    fn to_flat_vec_f64(&self) -> Vec<f64> {
+        let flat = self.tensor().to_device(tch::Device::Cpu).flatten(0, -1);
+        Vec::try_from(flat.to_kind(tch::Kind::Double))
+            .expect("a flat Double tensor on the CPU converts to Vec<f64>")
+    }
+
+    /// Return the elements of the wrapped tensor in row-major order as `i64`s, converting them
+    /// from the tensor's kind. Floating point elements are truncated toward zero.
+    fn to_flat_vec_i64(&self) -> Vec<i64> {
+        let flat = self.tensor().to_device(tch::Device::Cpu).flatten(0, -1);
+        Vec::try_from(flat.to_kind(tch::Kind::Int64))
+            .expect("a flat Int64 tensor on the CPU converts to Vec<i64>")
+    }
+
+    /// Return the element at `indices` as a scalar tensor, as in `x.get_element(&[0, 1, 2])`.
+    /// This is shorthand for a chain of `tch::Tensor::get` calls, useful for checking values in
+    /// tests. A RankMismatch error, expecting the rank of the wrapped tensor and finding the number
+    /// of indices, is returned if they differ. As with `tch::Tensor::get`, a negative index counts
+    /// back from the end of its axis, and an IndexOutOfRange error is returned if an index is out
+    /// of range.
+    fn get_element(&self, indices: &[i64]) -> Result<tch::Tensor, crate::TensorTypeError> {
+        let size = self.tensor().size();
+        if indices.len() != size.len() {
+            return Err(crate::TensorTypeError::RankMismatch {
+                type_name: Self::NAME.to_string(),
+                expected_rank: size.len(),
+                found_rank: indices.len(),
+            });
+        }
+        for (axis, (&index, &dim_size)) in indices.iter().zip(&size).enumerate() {
+            if !(-dim_size..dim_size).contains(&index) {
+                return Err(crate::TensorTypeError::IndexOutOfRange {
+                    type_name: Self::NAME.to_string(),
+                    axis,
+                    index,
+                    size: dim_size,
+                });
+            }
+        }
+        Ok(indices
+            .iter()
+            .fold(self.tensor().shallow_clone(), |t, &i| t.get(i)))
+    }
+
+    /// Create an instance from an ndarray array, converting its elements to the creation kind.
+    /// The array's shape is checked as by `new()`, so a ShapeMismatch error is returned if it
+    /// doesn't match. Requires the `ndarray` feature.
+    #[cfg(feature = "ndarray")]
+    fn from_ndarray<S, D>(
+        array: &ndarray::ArrayBase<S, D>,
+        params: &Self::InnerType,
+    ) -> Result<Self, crate::TensorTypeError>
+    where
+        S: ndarray::Data,
+        S::Elem: tch::kind::Element + Clone,
+        D: ndarray::Dimension,
+        Self: Sized,
+    {
+        let data: Vec<S::Elem> = array.iter().cloned().collect();
+        let shape: Vec<i64> = array.shape().iter().map(|&dim| dim as i64).collect();
+        let tensor = tch::Tensor::from_slice(&data)
+            .reshape(shape)
+            .to_kind(Self::creation_kind(params)?);
+        Self::new(tensor, params)
+    }
+
+    /// Return the kinds the wrapped tensor has had, oldest first, for tracking down where the
+    /// precision changes. The history is recorded only with the `cast-history` feature, and is
+    /// empty otherwise. It starts with the kind of the tensor a value is created with, and
+    /// `apply_fn()` and `to_kind_as()` add the kind of their result if it changed. Other
+    /// conversions start a new history.
+    fn kind_history(&self) -> &[tch::Kind] {
+        &[]
+    }
+
+    /// Continue the kind history of the value this one was derived from. Only tensor types that
+    /// record a history implement this.
+    #[doc(hidden)]
+    fn extend_kind_history(&mut self, _previous: &[tch::Kind]) {}
+
+    /// Return a key identifying the type, shape, and kind of the wrapped tensor, but not its
+    /// contents. Two instances of the same type with the same shape and kind have equal keys, so
+    /// the key can be used in a cache keyed by shape without hashing the tensor's data. The tensor
+    /// types don't implement Hash and Eq by default: this is shape identity, not value identity,
+    /// which would be inconsistent with `#[partial_eq]`. Types marked with `#[shape_eq]` instead
+    /// implement PartialEq, Eq, and Hash by comparing and hashing their keys.
+    fn shape_key(&self) -> crate::ShapeKey {
+        crate::ShapeKey {
+            type_name: Self::NAME,
+            shape: self.tensor().size(),
+            kind: self.tensor().kind(),
+        }
+    }
+
+    /// Return a `DeserializeSeed` that deserializes a value of this type, checking it against
+    /// `params`.
+    ///
+    /// # Example
+    /// ```
+    /// use serde::de::DeserializeSeed;
+    /// use tensor_types::{tensor_type, TensorType};
+    /// use tch::{Device, Kind, Tensor};
+    ///
+    /// pub struct Params {
+    ///     rows: i64,
+    ///     cols: i64,
+    /// }
+    /// tensor_type!(#[partial_eq] Matrix, [rows, cols], Params, Kind::Float);
+    ///
+    /// let params = Params { rows: 2, cols: 3 };
+    /// let m = Matrix::new(Tensor::randn([2, 3], (Kind::Float, Device::Cpu)), &params)?;
+    /// let json = serde_json::to_string(&m)?;
+    ///
+    /// let mut deserializer = serde_json::Deserializer::from_str(&json);
+    /// let restored = Matrix::seed(&params).deserialize(&mut deserializer)?;
+    /// assert_eq!(m, restored);
+    /// # Ok::<(), anyhow::Error>(())
+    /// ```
+    fn seed(params: &Self::InnerType) -> crate::TensorSeed<'_, Self>
+    where
+        Self: Sized,
+    {
+        crate::TensorSeed::new(params)
+    }
+}
+
+/// Check that a tensor has the shape of a tensor type, without wrapping it. It evaluates to a
+/// `Result<(), TensorTypeError>` holding the same error that `new()` would return for the shape.
+/// This is useful for checking intermediate results of tch::Tensor operations.
+///
+/// # Example
+/// ```
+/// use tensor_types::{assert_shape, tensor_type};
+/// use tch::{Device, Kind, Tensor};
+///
+/// pub struct Params {
+///     rows: i64,
+///     cols: i64,
+/// }
+/// tensor_type!(Matrix, [rows, cols], Params, Kind::Float);
+///
+/// let params = Params { rows: 2, cols: 3 };
+/// let t = Tensor::randn([2, 3], (Kind::Float, Device::Cpu));
+/// assert_shape!(Matrix, &t, &params)?;
+/// assert!(assert_shape!(Matrix, &t.transpose(0, 1), &params).is_err());
+/// # Ok::<(), tensor_types::TensorTypeError>(())
+/// ```
+#[macro_export]
+macro_rules! assert_shape {
+    ($type:ty, $tensor:expr, $params:expr) => {
+        <$type as $crate::TensorType>::check_shape($tensor, $params)
+    };
+}
+
+/// Declare several tensor types sharing one parameters type, as `Name => [fields]: kind;` entries
+/// following the parameters type. Each entry expands to a `tensor_type!` invocation, so the shape
+/// may use any of the list forms, the kind any of the kind forms, such as `[Kind::Float,
+/// Kind::Double]`, `KindClass::AnyFloat`, or `kind = field`, and options may follow the kind. An
+/// entry may be marked with `#[public_tensor]`, `#[partial_eq]`, or `#[shape_eq]`.
+///
+/// # Example
+/// ```
+/// use tensor_types::{tensor_types, TensorType};
+/// use tch::{Device, Kind, Tensor};
+///
+/// pub struct Params {
+///     batch_size: i64,
+///     seq_len: i64,
+///     d_model: i64,
+/// }
+/// tensor_types! {
+///     Params;
+///     BatchSeqDModel => [batch_size, seq_len, d_model]: Kind::Float;
+///     BatchSeq => [batch_size, seq_len]: Kind::Int64;
+///     Bias => [batch_size, seq_len, d_model]: Kind::Float, broadcast = true;
+/// }
+///
+/// let params = Params { batch_size: 2, seq_len: 3, d_model: 4 };
+/// let tokens = BatchSeq::new(Tensor::zeros([2, 3], (Kind::Int64, Device::Cpu)), &params)?;
+/// assert_eq!(tokens.size(), &[2, 3]);
+/// # Ok::<(), tensor_types::TensorTypeError>(())
+/// ```
+#[macro_export]
+macro_rules! tensor_types {
+    // The entries, one at a time. The head of each is the name, with its markers, such as
+    // #[public_tensor], if given.
+    (@entries ($params:ty)) => {};
+    (@entries ($params:ty) $(#[$marker:ident])* $name:ident => [$($dim:tt)*]: $($rest:tt)+) => {
+        $crate::tensor_types!(@kind ($params) ($(#[$marker])* $name) [$($dim)*] () $($rest)+);
+    };
+
+    // The kind and options of an entry, collected token by token up to the `;` ending the entry,
+    // and forwarded unchanged, so that tensor_type! parses them as it does for a single type.
+    (@kind ($params:ty) ($($head:tt)+) $shape:tt ($($kind:tt)+) ; $($rest:tt)*) => {
+        $crate::tensor_type!($($head)+, $shape, $params, $($kind)+);
+        $crate::tensor_types!(@entries ($params) $($rest)*);
+    };
+    (@kind ($params:ty) ($($head:tt)+) $shape:tt ($($kind:tt)+)) => {
+        $crate::tensor_type!($($head)+, $shape, $params, $($kind)+);
+    };
+    (@kind ($params:ty) ($($head:tt)+) $shape:tt ($($kind:tt)*) $next:tt $($rest:tt)*) => {
+        $crate::tensor_types!(@kind ($params) ($($head)+) $shape ($($kind)* $next) $($rest)*);
+    };
+
+    ($params:ty; $($rest:tt)+) => {
+        $crate::tensor_types!(@entries ($params) $($rest)+);
+    };
+}
+
+/// Log the type name, shape, kind, and device of a tensor that has been checked by `new()`, at the
+/// debug level. Requires the `trace` feature; without it, this does nothing.
+#[doc(hidden)]
+#[inline(always)]
+pub fn trace_new(type_name: &str, tensor: &tch::Tensor) {
+    #[cfg(feature = "trace")]
+    log::debug!(
+        "{type_name}::new(): {:?} ({:?}, {:?})",
+        tensor.size(),
+        tensor.kind(),
+        tensor.device()
+    );
+    #[cfg(not(feature = "trace"))]
+    let _ = (type_name, tensor);
+}
+
+/// A field value giving a dimension. Values implementing DimensionValue, such as the parameter
+/// types and the integer types, are converted with `try_to_dimension()`, so that a value out of
+/// the range of `i64` gives a DimensionOverflow error rather than a panic. Other values are
+/// converted with `Into<i64>`. The conversion is chosen by method resolution, which prefers the
+/// impl of CheckedDimension for `Dimension<T>` to the one for `&Dimension<T>`.
+///
+/// The fallback impl applies to every `T`, and its `Into<i64>` bound is on the method rather than
+/// the impl, so that a value that can't be converted gives the usual unsatisfied `From` bound
+/// error rather than a method resolution error naming these items.
+#[doc(hidden)]
+pub struct Dimension<T>(pub T);
+
+#[doc(hidden)]
+pub trait CheckedDimension {
+    type Value;
+
+    fn dimension(&self, type_name: &str) -> Result<i64, TensorTypeError>
+    where
+        Self::Value: Into<i64> + Copy;
+}
+
+impl<T: crate::DimensionValue + std::fmt::Debug> CheckedDimension for Dimension<T> {
+    type Value = i64;
+
+    fn dimension(&self, type_name: &str) -> Result<i64, TensorTypeError> {
+        self.0
+            .try_to_dimension()
+            .ok_or_else(|| TensorTypeError::DimensionOverflow {
+                type_name: type_name.to_string(),
+                value: format!("{:?}", self.0),
+            })
+    }
+}
+
+impl<T> CheckedDimension for &Dimension<T> {
+    type Value = T;
+
+    fn dimension(&self, _type_name: &str) -> Result<i64, TensorTypeError>
+    where
+        T: Into<i64> + Copy,
+    {
+        Ok(self.0.into())
+    }
+}
+
+/// Compute a dimension given as a sum of products of field values, as in `seq_len * d_model`,
+/// returning a DimensionOverflow error if the result is out of the range of `i64`.
+#[doc(hidden)]
+pub fn checked_dimension(type_name: &str, terms: &[&[i64]]) -> Result<i64, TensorTypeError> {
+    terms
+        .iter()
+        .try_fold(0i64, |sum, factors| {
+            let product = factors
+                .iter()
+                .try_fold(1i64, |product, &factor| product.checked_mul(factor))?;
+            sum.checked_add(product)
+        })
+        .ok_or_else(|| TensorTypeError::DimensionOverflow {
+            type_name: type_name.to_string(),
+            value: terms
+                .iter()
+                .map(|factors| {
+                    let factors: Vec<String> = factors.iter().map(i64::to_string).collect();
+                    factors.join(" * ")
+                })
+                .collect::<Vec<_>>()
+                .join(" + "),
+        })
+}
+
+/// Compute the number of elements of a tensor of the given size, returning a DimensionOverflow
+/// error if it's out of the range of `i64`.
+pub(crate) fn checked_numel(type_name: &str, size: &[i64]) -> Result<i64, TensorTypeError> {
+    size.iter()
+        .try_fold(1i64, |numel, &dim| numel.checked_mul(dim))
+        .ok_or_else(|| TensorTypeError::DimensionOverflow {
+            type_name: type_name.to_string(),
+            value: format!("the number of elements of {size:?}"),
+        })
+}
+
+/// Map the result of a fallible tch operation on a tensor of the named type, returning a Tch error
+/// if it failed.
+pub(crate) fn tch_result<T>(
+    type_name: &str,
+    result: Result<T, tch::TchError>,
+) -> Result<T, TensorTypeError> {
+    result.map_err(|source| TensorTypeError::Tch {
+        type_name: type_name.to_string(),
+        source,
+    })
+}
+
+/// Return the size of `axis` of a tensor, returning an InvalidAxis error if it's out of range.
+pub(crate) fn axis_size(
+    type_name: &str,
+    tensor: &tch::Tensor,
+    axis: usize,
+) -> Result<i64, TensorTypeError> {
+    let size = tensor.size();
+    size.get(axis)
+        .copied()
+        .ok_or_else(|| TensorTypeError::InvalidAxis {
+            type_name: type_name.to_string(),
+            axis: axis as i64,
+            rank: size.len(),
+        })
+}
+
+/// Apply an arithmetic operator to the wrapped tensors of two values of the same tensor type. The
+/// shape of one operand must end with the shape of the other, as for values of a type with the
+/// broadcast option created with the same parameters, so that the result has the larger shape.
+/// The result must also have the kind of one of the operands. Both operands were checked when they
+/// were created, so the result then matches the type without the parameters. The operators can't
+/// return an error, so this panics otherwise.
+#[doc(hidden)]
+pub fn checked_binary_op<F>(
+    type_name: &str,
+    op: &str,
+    lhs: &tch::Tensor,
+    rhs: &tch::Tensor,
+    tfn: F,
+) -> tch::Tensor
+where
+    F: FnOnce(&tch::Tensor, &tch::Tensor) -> tch::Tensor,
+{
+    let (lhs_size, rhs_size) = (lhs.size(), rhs.size());
+    if !lhs_size.ends_with(&rhs_size) && !rhs_size.ends_with(&lhs_size) {
+        panic!(
+            "shape mismatch in {op} on TensorType {type_name:?}: the operands have shapes {lhs_size:?} and {rhs_size:?}"
+        );
+    }
+    let result = tfn(lhs, rhs);
+    let (lhs_kind, rhs_kind, kind) = (lhs.kind(), rhs.kind(), result.kind());
+    if kind != lhs_kind && kind != rhs_kind {
+        panic!(
+            "kind mismatch in {op} on TensorType {type_name:?}: {lhs_kind:?} and {rhs_kind:?} operands give {kind:?}"
+        );
+    }
+    result
+}
+
+/// Whether `new()` skips its checks in builds without debug assertions, as enabled by the
+/// `unchecked-release` feature. Debug assertions are tested in the expansion of `new()`, so that
+/// the setting of the calling crate applies.
+#[doc(hidden)]
+pub const UNCHECKED_RELEASE: bool = cfg!(feature = "unchecked-release");
+
+/// Options that adjust the checks made by a tensor type. They're given as trailing `name = value`
+/// arguments to the tensor_type!() macro, e.g.
+/// `tensor_type!(Bias, [d_model], Params, Kind::Float, broadcast = true)`. Options that aren't
+/// given keep their default values.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub struct TensorTypeOptions {
+    /// Accept tensors whose trailing dimensions match the declared shape but whose leading
+    /// dimensions are absent, such as a `[d_model]` bias that broadcasts against a
+    /// `[batch, seq, d_model]` type. For `rank = N` types, any rank up to N is accepted.
+    pub broadcast: bool,
+
+    /// What to do when a tensor modified through `tensor_mut()` no longer matches the type.
+    pub on_invalid_mut: InvalidMutAction,
+
+    /// Whether the tensor must require gradients, as for parameters, or must not, as for inputs.
+    /// It's given as `requires_grad = true` or `requires_grad = false`. When it's not given,
+    /// requires_grad isn't checked.
+    pub requires_grad: Option<bool>,
+
+    /// Reject tensors that aren't contiguous in memory, as required by some kernels. The tensor
+    /// isn't made contiguous, so that the caller knows where a non-contiguous tensor came from.
+    pub contiguous: bool,
+}
+
+impl TensorTypeOptions {
+    /// The default options: the tensor's shape must match exactly, and an invalid mutation panics.
+    pub const DEFAULT: Self = Self {
+        broadcast: false,
+        on_invalid_mut: InvalidMutAction::Panic,
+        requires_grad: None,
+        contiguous: false,
+    };
+}
+
+impl Default for TensorTypeOptions {
+    fn default() -> Self {
+        Self::DEFAULT
+    }
+}
+
+/// The identity of a tensor type's shape, returned by `shape_key()`. Keys compare the type name,
+/// shape, and kind, not the tensor's values, so they implement Eq and Hash where the tensor types
+/// themselves can only implement PartialEq.
+#[derive(Clone, Debug, PartialEq, Eq, Hash)]
+pub struct ShapeKey {
+    pub type_name: &'static str,
+    pub shape: Vec<i64>,
+    pub kind: tch::Kind,
+}
+
+/// A category of kinds, given as the kind of a tensor type, e.g.
+/// `tensor_type!(MyTensor, [rows], Params, KindClass::AnyFloat)`, so that a tensor of any kind in
+/// the category is accepted. The first kind of the category is used to create tensors.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum KindClass {
+    /// The floating point kinds: Float, Double, Half, and BFloat16.
+    AnyFloat,
+    /// The integer kinds: Int64, Int, Int16, Int8, and Uint8.
+    AnyInt,
+}
+
+impl KindClass {
+    /// The kinds in the category.
+    pub const fn kinds(self) -> &'static [tch::Kind] {
+        match self {
+            KindClass::AnyFloat => &[
+                tch::Kind::Float,
+                tch::Kind::Double,
+                tch::Kind::Half,
+                tch::Kind::BFloat16,
+            ],
+            KindClass::AnyInt => &[
+                tch::Kind::Int64,
+                tch::Kind::Int,
+                tch::Kind::Int16,
+                tch::Kind::Int8,
+                tch::Kind::Uint8,
+            ],
+        }
+    }
+
+    /// The category whose kinds are exactly `kinds`, if any.
+    pub fn of(kinds: &[tch::Kind]) -> Option<KindClass> {
+        [KindClass::AnyFloat, KindClass::AnyInt]
+            .into_iter()
+            .find(|class| class.kinds() == kinds)
+    }
+}
+
+impl std::fmt::Display for KindClass {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            KindClass::AnyFloat => write!(f, "any floating point kind"),
+            KindClass::AnyInt => write!(f, "any integer kind"),
+        }
+    }
+}
+
+/// The kinds a value's tensor has had, as returned by `kind_history()`. They're recorded only with
+/// the `cast-history` feature; without it, this has no size and records nothing, so that
+/// creating a value doesn't call `kind()`.
+#[doc(hidden)]
+#[derive(Clone, Debug, Default)]
+pub struct KindHistory {
+    #[cfg(feature = "cast-history")]
+    kinds: Vec<tch::Kind>,
+}
+
+impl KindHistory {
+    /// Start a history with the kind of the tensor.
+    pub fn new(tensor: &tch::Tensor) -> Self {
+        Self::extended(&[], tensor)
+    }
+
+    /// Continue a previous history with the kind of the tensor, if it differs from the last.
+    pub fn extended(previous: &[tch::Kind], tensor: &tch::Tensor) -> Self {
+        #[cfg(feature = "cast-history")]
+        {
+            let mut kinds = previous.to_vec();
+            let kind = tensor.kind();
+            if kinds.last() != Some(&kind) {
+                kinds.push(kind);
+            }
+            Self { kinds }
+        }
+        #[cfg(not(feature = "cast-history"))]
+        {
+            let _ = (previous, tensor);
+            Self {}
+        }
+    }
+
+    /// The kinds recorded, oldest first.
+    pub fn kinds(&self) -> &[tch::Kind] {
+        #[cfg(feature = "cast-history")]
+        return &self.kinds;
+        #[cfg(not(feature = "cast-history"))]
+        &[]
+    }
+}
+
+/// The action taken when the guard returned by `tensor_mut()` is dropped and the tensor no longer
+/// matches its type.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub enum InvalidMutAction {
+    /// Panic with the check's error. This is skipped if the thread is already panicking.
+    Panic,
+    /// Log the check's error at the warn level through the `log` crate and continue. Logging
+    /// requires the `trace` feature; without it, the error is ignored, so call `verify()` to
+    /// check the tensor.
+    Warn,
+}
+
+/// A guard giving mutable access to the tensor wrapped by a tensor type, returned by
+/// `tensor_mut()`. It dereferences to the tch::Tensor. When it's dropped, the tensor is checked
+/// again, so that in-place operations can't silently break the type's shape or kind.
+pub struct TensorMut<'a, P: ?Sized> {
+    tensor: &'a mut tch::Tensor,
+    params: &'a P,
+    check: fn(&tch::Tensor, &P) -> Result<(), TensorTypeError>,
+    on_invalid: InvalidMutAction,
+}
+
+impl<'a, P: ?Sized> TensorMut<'a, P> {
+    #[doc(hidden)]
+    pub fn new(
+        tensor: &'a mut tch::Tensor,
+        params: &'a P,
+        check: fn(&tch::Tensor, &P) -> Result<(), TensorTypeError>,
+        on_invalid: InvalidMutAction,
+    ) -> Self {
+        Self {
+            tensor,
+            params,
+            check,
+            on_invalid,
+        }
+    }
+}
+
+/// A wrapper borrowing a tensor that has been checked against the tensor type `T`, returned by
+/// `new_ref()`. It dereferences to the tch::Tensor. The tensor can't be modified through it, so it
+/// remains of type `T` for as long as it's borrowed.
+pub struct TensorRef<'a, T> {
+    tensor: &'a tch::Tensor,
+    marker: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<'a, T> TensorRef<'a, T> {
+    #[doc(hidden)]
+    pub fn new(tensor: &'a tch::Tensor) -> Self {
+        Self {
+            tensor,
+            marker: std::marker::PhantomData,
+        }
+    }
+
+    /// Return the borrowed tensor, with the lifetime of the borrow.
+    pub fn tensor(&self) -> &'a tch::Tensor {
+        self.tensor
+    }
+}
+
+impl<T> std::ops::Deref for TensorRef<'_, T> {
+    type Target = tch::Tensor;
+
+    fn deref(&self) -> &Self::Target {
+        self.tensor
+    }
+}
+
+impl<T: TensorType> std::fmt::Debug for TensorRef<'_, T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        write!(f, "TensorRef<{}>({:?})", T::NAME, self.tensor)
+    }
+}
+
+impl<P: ?Sized> std::ops::Deref for TensorMut<'_, P> {
+    type Target = tch::Tensor;
+
+    fn deref(&self) -> &Self::Target {
+        self.tensor
+    }
+}
+
+impl<P: ?Sized> std::ops::DerefMut for TensorMut<'_, P> {
+    fn deref_mut(&mut self) -> &mut Self::Target {
+        self.tensor
+    }
+}
+
+impl<P: ?Sized> Drop for TensorMut<'_, P> {
+    fn drop(&mut self) {
+        if let Err(err) = (self.check)(self.tensor, self.params) {
+            match self.on_invalid {
+                InvalidMutAction::Panic if !std::thread::panicking() => {
+                    panic!("invalid mutation through tensor_mut(): {err}")
+                }
+                InvalidMutAction::Panic => {}
+                InvalidMutAction::Warn => {
+                    #[cfg(feature = "trace")]
+                    log::warn!("invalid mutation through tensor_mut(): {err}");
+                }
+            }
+        }
+    }
 }
 
 #[macro_export]
 macro_rules! tensor_type {
 
-    ($name:ident, [$($field:ident),*], $params:ty, $kind:expr) => {
+    // Element-wise arithmetic between two values of the same tensor type, implemented only for the
+    // fixed [fields] forms. Valid values of the rank, dynamic, and optional leading dimension forms
+    // may differ in shape, so those types use the checked_add() etc. methods instead. Values of
+    // a fixed form with the broadcast option are suffixes of the same shape, and broadcast to the
+    // larger one. Values created with different parameters may still differ, so the shapes are
+    // checked, as is the kind of the result, and the operator panics if either would give a value
+    // that doesn't match the type.
+    (@binary_ops $name:ident, optional_batch [$($dim:tt)*]) => {};
+    (@binary_ops $name:ident, dynamic) => {};
+    (@binary_ops $name:ident, rank = $rank:expr) => {};
+    (@binary_ops $name:ident, $($shape:tt)*) => {
+        $crate::tensor_type!(@binary_op $name, Add, add, |lhs, rhs| lhs + rhs);
+        $crate::tensor_type!(@binary_op $name, Sub, sub, |lhs, rhs| lhs - rhs);
+        $crate::tensor_type!(@binary_op $name, Mul, mul, |lhs, rhs| lhs * rhs);
+        // Integer tensors are divided with truncation, as for Rust integers, so that the kind is
+        // preserved. Torch's true division would otherwise return a Float tensor. Also as for
+        // Rust integers, dividing by a zero element panics where torch reports it, as on the CPU.
+        // Elsewhere, the quotient of those elements is unspecified. Floating point division by
+        // zero gives infinity or NaN. The quotient is checked like the other operators' results.
+        $crate::tensor_type!(@binary_op $name, Div, div, |lhs, rhs| if lhs.is_floating_point() {
+            lhs / rhs
+        } else {
+            lhs.f_divide_tensor_mode(rhs, "trunc").unwrap_or_else(|error| {
+                panic!("integer division on TensorType {:?} failed: {error}", stringify!($name))
+            })
+        });
+    };
+    (@binary_op $name:ident, $trait:ident, $method:ident, |$lhs:ident, $rhs:ident| $op:expr) => {
+        impl std::ops::$trait for &$name {
+            type Output = $name;
+
+            fn $method(self, rhs: Self) -> $name {
+                let tensor = $crate::checked_binary_op(
+                    stringify!($name),
+                    stringify!($trait),
+                    &self.tensor,
+                    &rhs.tensor,
+                    |$lhs, $rhs| $op,
+                );
+                <$name as $crate::TensorType>::new_unchecked(tensor)
+            }
+        }
+
+        impl std::ops::$trait for $name {
+            type Output = $name;
+
+            fn $method(self, rhs: Self) -> $name {
+                std::ops::$trait::$method(&self, &rhs)
+            }
+        }
+    };
+
+    // The shape checks. Each returns a ShapeMismatch or RankMismatch error from the enclosing
+    // function if the tensor doesn't have the required shape. The expected size is given by a
+    // closure, so that it's only computed by the forms that need it.
+    (@check_shape $name:ident, $tensor:ident, $expected_size:ident, []) => {
+        // An empty list of fields declares a scalar, a tensor of rank 0.
+        let _ = $expected_size;
+        if !$tensor.size().is_empty() {
+            return Err($crate::TensorTypeError::RankMismatch {
+                type_name: stringify!($name).to_string(),
+                expected_rank: 0,
+                found_rank: $tensor.dim(),
+            });
+        }
+    };
+    (@check_shape $name:ident, $tensor:ident, $expected_size:ident, [$($dim:tt)*]) => {
+        $crate::tensor_type!(@check_size $name, $tensor, $expected_size, $crate::tensor_type!(@dim_names [$($dim)*]));
+    };
+    (@check_shape $name:ident, $tensor:ident, $expected_size:ident, rounded [$($dim:tt)*]) => {
+        $crate::tensor_type!(@check_shape $name, $tensor, $expected_size, [$($dim)*]);
+    };
+    (@check_shape $name:ident, $tensor:ident, $expected_size:ident, getters [$($dim:tt)*]) => {
+        $crate::tensor_type!(@check_shape $name, $tensor, $expected_size, [$($dim)*]);
+    };
+    // A tensor without the optional leading dimension is accepted if the rest of its dimensions
+    // match. Otherwise it's checked against the full shape, so errors report the full shape.
+    (@check_shape $name:ident, $tensor:ident, $expected_size:ident, optional_batch [$($dim:tt)*]) => {
+        let expected_size: Vec<i64> = $expected_size()?;
+        if $tensor.size() != expected_size[1..] {
+            let expected_size = || Ok::<_, $crate::TensorTypeError>(expected_size);
+            $crate::tensor_type!(@check_shape $name, $tensor, expected_size, [$($dim)*]);
+        }
+    };
+    // The dimensions of a dynamic type have no names, so errors report the axis by index.
+    (@check_shape $name:ident, $tensor:ident, $expected_size:ident, dynamic) => {
+        $crate::tensor_type!(@check_size $name, $tensor, $expected_size, vec![]);
+    };
+    (@check_shape $name:ident, $tensor:ident, $expected_size:ident, rank = $rank:expr) => {
+        let _ = $expected_size;
+        let found_rank = $tensor.dim();
+        let rank_ok = if <$name as $crate::TensorType>::OPTIONS.broadcast {
+            found_rank <= $rank
+        } else {
+            found_rank == $rank
+        };
+        if !rank_ok {
+            return Err($crate::TensorTypeError::RankMismatch {
+                type_name: stringify!($name).to_string(),
+                expected_rank: $rank,
+                found_rank,
+            });
+        }
+    };
+
+    // The check of the tensor's size against the expected size, shared by the forms that give
+    // the dimensions.
+    (@check_size $name:ident, $tensor:ident, $expected_size:ident, $dim_names:expr) => {
+        let tensor_size = $tensor.size();
+        let expected_size = $expected_size()?;
+
+        let shape_ok = if <$name as $crate::TensorType>::OPTIONS.broadcast {
+            expected_size.ends_with(&tensor_size)
+        } else {
+            tensor_size == expected_size
+        };
+        if !shape_ok {
+            return Err($crate::TensorTypeError::ShapeMismatch {
+                type_name: stringify!($name).to_string(),
+                dim_names: $dim_names,
+                expected: expected_size,
+                found: tensor_size
+            });
+        }
+    };
+
+    // The names of the dimensions given by the fields, such as "batch_size" or "seq_len * d_model".
+    // The forms that don't name their dimensions have no names.
+    (@dim_names [$($head:ident $(* $tail:ident)* $(+ $shead:ident $(* $stail:ident)*)* $(in $min:literal ..= $max:literal)?),*]) => {
+        vec![$(stringify!($head $(* $tail)* $(+ $shead $(* $stail)*)*).to_string()),*]
+    };
+    (@dim_names rounded [$($dim:tt)*]) => { $crate::tensor_type!(@dim_names [$($dim)*]) };
+    (@dim_names getters [$($dim:tt)*]) => { $crate::tensor_type!(@dim_names [$($dim)*]) };
+    (@dim_names optional_batch [$($dim:tt)*]) => { $crate::tensor_type!(@dim_names [$($dim)*]) };
+    (@dim_names $($shape:tt)*) => { Vec::<String>::new() };
+
+    // The dimensions given by each form of the shape. Tensor dimensions must be positive, so an
+    // InvalidDimension error is returned for a zero or negative dimension.
+    //
+    // A dimension may be a sum of products of fields, e.g. seq_len * d_model, and may be followed
+    // by inclusive bounds, e.g. batch_size in 1..=4096. The sums and products are checked, and a
+    // DimensionOverflow error is returned if one is out of the range of i64.
+    (@expected_size $name:ident, $params:ident, []) => {{
+        let _ = $params;
+        Ok(vec![])
+    }};
+    (@expected_size $name:ident, $params:ident, [$($head:ident $(* $tail:ident)* $(+ $shead:ident $(* $stail:ident)*)* $(in $min:literal ..= $max:literal)?),*]) => {{
+        use $crate::CheckedDimension as _;
+        $crate::tensor_type!(@positive_dimensions $name, vec![$(
+            $crate::checked_dimension(stringify!($name), &[
+                &[$crate::tensor_type!(@dimension $name, $params.$head) $(, $crate::tensor_type!(@dimension $name, $params.$tail))*]
+                $(, &[$crate::tensor_type!(@dimension $name, $params.$shead) $(, $crate::tensor_type!(@dimension $name, $params.$stail))*])*
+            ])?
+        ),*], vec![$($crate::tensor_type!(@bounds $($min $max)?)),*])
+    }};
+    // Floating point fields are rounded to the nearest integer. A DimensionOverflow error is
+    // returned if a dimension is NaN, infinite, or out of the range of i64, and a
+    // NonIntegralDimension error if it's more than 1e-6 from an integer.
+    (@expected_size $name:ident, $params:ident, rounded [$($head:ident $(* $tail:ident)* $(+ $shead:ident $(* $stail:ident)*)* $(in $min:literal ..= $max:literal)?),*]) => {{
+        let values: Vec<f64> = vec![$(
+            f64::from($params.$head) $(* f64::from($params.$tail))*
+                $(+ f64::from($params.$shead) $(* f64::from($params.$stail))*)*
+        ),*];
+        let mut expected_size = Vec::with_capacity(values.len());
+        for (axis, value) in values.into_iter().enumerate() {
+            // i64::MAX as f64 is 2^63, which is out of range, so the upper bound is exclusive.
+            if !value.is_finite() || value.round() < i64::MIN as f64 || value.round() >= i64::MAX as f64 {
+                return Err($crate::TensorTypeError::DimensionOverflow {
+                    type_name: stringify!($name).to_string(),
+                    value: value.to_string(),
+                });
+            }
+            if (value - value.round()).abs() > 1e-6 {
+                return Err($crate::TensorTypeError::NonIntegralDimension {
+                    type_name: stringify!($name).to_string(),
+                    axis,
+                    value,
+                });
+            }
+            expected_size.push(value.round() as i64);
+        }
+        $crate::tensor_type!(@positive_dimensions $name, expected_size, vec![$($crate::tensor_type!(@bounds $($min $max)?)),*])
+    }};
+    // The dimensions given by getter methods of the parameters rather than by their fields.
+    (@expected_size $name:ident, $params:ident, getters [$($head:ident $(* $tail:ident)* $(+ $shead:ident $(* $stail:ident)*)* $(in $min:literal ..= $max:literal)?),*]) => {{
+        use $crate::CheckedDimension as _;
+        $crate::tensor_type!(@positive_dimensions $name, vec![$(
+            $crate::checked_dimension(stringify!($name), &[
+                &[$crate::tensor_type!(@dimension $name, $params.$head()) $(, $crate::tensor_type!(@dimension $name, $params.$tail()))*]
+                $(, &[$crate::tensor_type!(@dimension $name, $params.$shead()) $(, $crate::tensor_type!(@dimension $name, $params.$stail()))*])*
+            ])?
+        ),*], vec![$($crate::tensor_type!(@bounds $($min $max)?)),*])
+    }};
+    // The conversion of a field value to a dimension, returning a DimensionOverflow error from
+    // the enclosing function if it's out of the range of i64. See Dimension.
+    (@dimension $name:ident, $value:expr) => {
+        (&$crate::Dimension($value)).dimension(stringify!($name))?
+    };
+    // The dimensions of a type with an optional leading dimension include that dimension.
+    (@expected_size $name:ident, $params:ident, optional_batch [$($dim:tt)*]) => {
+        $crate::tensor_type!(@expected_size $name, $params, [$($dim)*])
+    };
+    // The dimensions of a dynamic type are the slice passed as its parameters.
+    (@expected_size $name:ident, $params:ident, dynamic) => {
+        $crate::tensor_type!(@positive_dimensions $name, $params.to_vec(), vec![])
+    };
+    (@expected_size $name:ident, $params:ident, rank = $rank:expr) => {{
+        let _ = $params;
+        Err($crate::TensorTypeError::UnknownDimensions {
+            type_name: stringify!($name).to_string(),
+        })
+    }};
+
+    // The same dimensions, with the value of each field looked up by name in a map. A
+    // MissingDimension error is returned if a field isn't in the map.
+    (@expected_size_from_map $name:ident, $map:ident, []) => {{
+        let _ = $map;
+        Ok(vec![])
+    }};
+    (@expected_size_from_map $name:ident, $map:ident, [$($head:ident $(* $tail:ident)* $(+ $shead:ident $(* $stail:ident)*)* $(in $min:literal ..= $max:literal)?),*]) => {{
+        let lookup = |field: &str| {
+            $map.get(field)
+                .copied()
+                .ok_or_else(|| $crate::TensorTypeError::MissingDimension {
+                    type_name: stringify!($name).to_string(),
+                    name: field.to_string(),
+                })
+        };
+        $crate::tensor_type!(@positive_dimensions $name, vec![$(
+            $crate::checked_dimension(stringify!($name), &[
+                &[lookup(stringify!($head))? $(, lookup(stringify!($tail))?)*]
+                $(, &[lookup(stringify!($shead))? $(, lookup(stringify!($stail))?)*])*
+            ])?
+        ),*], vec![$($crate::tensor_type!(@bounds $($min $max)?)),*])
+    }};
+    (@expected_size_from_map $name:ident, $map:ident, rounded [$($dim:tt)*]) => {
+        $crate::tensor_type!(@expected_size_from_map $name, $map, [$($dim)*])
+    };
+    (@expected_size_from_map $name:ident, $map:ident, optional_batch [$($dim:tt)*]) => {
+        $crate::tensor_type!(@expected_size_from_map $name, $map, [$($dim)*])
+    };
+    (@expected_size_from_map $name:ident, $map:ident, getters [$($dim:tt)*]) => {
+        $crate::tensor_type!(@expected_size_from_map $name, $map, [$($dim)*])
+    };
+    (@expected_size_from_map $name:ident, $map:ident, rank = $rank:expr) => {{
+        let _ = $map;
+        Err($crate::TensorTypeError::UnknownDimensions {
+            type_name: stringify!($name).to_string(),
+        })
+    }};
+    (@expected_size_from_map $name:ident, $map:ident, dynamic) => {{
+        let _ = $map;
+        Err($crate::TensorTypeError::UnknownDimensions {
+            type_name: stringify!($name).to_string(),
+        })
+    }};
+
+    // The checks of the dimensions given by the fields. Each dimension may have bounds, given as
+    // Some((min, max)), and a DimensionOutOfRange error is returned if it's outside them.
+    (@positive_dimensions $name:ident, $size:expr, $bounds:expr) => {{
+        let expected_size: Vec<i64> = $size;
+        if let Some(axis) = expected_size.iter().position(|&dim| dim < 1) {
+            return Err($crate::TensorTypeError::InvalidDimension {
+                type_name: stringify!($name).to_string(),
+                axis,
+                value: expected_size[axis],
+            });
+        }
+        let bounds: Vec<Option<(i64, i64)>> = $bounds;
+        for (axis, (&value, bound)) in expected_size.iter().zip(bounds).enumerate() {
+            if let Some((min, max)) = bound {
+                if !(min..=max).contains(&value) {
+                    return Err($crate::TensorTypeError::DimensionOutOfRange {
+                        type_name: stringify!($name).to_string(),
+                        axis,
+                        value,
+                        min,
+                        max,
+                    });
+                }
+            }
+        }
+        Ok(expected_size)
+    }};
+    (@bounds) => { None };
+    (@bounds $min:literal $max:literal) => { Some(($min, $max)) };
+
+    // The kinds accepted by the type. They're either a constant set of kinds, given as
+    // `const [kinds]`, the kind given by a field of the parameters, given as `param field`, or the
+    // set of kinds given by a field of the parameters, given as `param_set field`.
+    (@kinds_const const [$($kind:expr),+]) => { &[$($kind),+] };
+    (@kinds_const class $class:ident) => { $crate::KindClass::$class.kinds() };
+    (@kinds_const param $field:ident) => { &[] };
+    (@kinds_const param_set $field:ident) => { &[] };
+    (@allowed_kinds $params:ident, const [$($kind:expr),+]) => {{
+        let _ = $params;
+        vec![$($kind),+]
+    }};
+    (@allowed_kinds $params:ident, class $class:ident) => {{
+        let _ = $params;
+        $crate::KindClass::$class.kinds().to_vec()
+    }};
+    (@allowed_kinds $params:ident, param $field:ident) => { vec![$params.$field] };
+    (@allowed_kinds $params:ident, param_set $field:ident) => { $params.$field.to_vec() };
+    (@creation_kind $name:ident, $params:ident, const [$first:expr $(, $kind:expr)*]) => {{
+        let _ = $params;
+        Ok($first)
+    }};
+    (@creation_kind $name:ident, $params:ident, class $class:ident) => {{
+        let _ = $params;
+        Ok($crate::KindClass::$class.kinds()[0])
+    }};
+    (@creation_kind $name:ident, $params:ident, param $field:ident) => { Ok($params.$field) };
+    (@creation_kind $name:ident, $params:ident, param_set $field:ident) => {
+        $params.$field.first().copied().ok_or_else(|| $crate::TensorTypeError::EmptyKindSet {
+            type_name: stringify!($name).to_string(),
+        })
+    };
+
+    // expected_kind() is generated only for types whose kinds don't depend on the parameters.
+    (@expected_kind_impl $name:ident, const [$first:expr $(, $kind:expr)*]) => {
+        impl $name {
+            /// The expected_kind() function returns the kind used when creating tensors of this
+            /// type, the first declared kind, without needing the parameters or a tensor.
+            pub fn expected_kind() -> tch::Kind {
+                $first
+            }
+        }
+    };
+    (@expected_kind_impl $name:ident, class $class:ident) => {
+        impl $name {
+            /// The expected_kind() function returns the kind used when creating tensors of this
+            /// type, the first kind of the class, without needing the parameters or a tensor.
+            pub fn expected_kind() -> tch::Kind {
+                $crate::KindClass::$class.kinds()[0]
+            }
+        }
+    };
+    (@expected_kind_impl $name:ident, $($kinds:tt)*) => {};
+
+    // PartialEq, generated only for types marked with #[partial_eq], so that a type can have its
+    // own instead.
+    (@partial_eq_impl $name:ident) => {
+        /// Two values of the same tensor type are equal if their wrapped tensors have the same
+        /// kind, device, shape, and elements. Tensors of different kinds or on different devices
+        /// aren't compared, and are unequal.
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                self.tensor.kind() == other.tensor.kind()
+                    && self.tensor.device() == other.tensor.device()
+                    && self.tensor.f_equal(&other.tensor).unwrap_or(false)
+            }
+        }
+    };
+
+    // PartialEq, Eq, and Hash by shape identity, generated only for types marked with #[shape_eq],
+    // so that values can be used as the keys of a map or set. They compare the keys returned by
+    // shape_key(), so they're consistent with each other, unlike the data comparison of
+    // #[partial_eq], which isn't reflexive for NaN elements.
+    (@shape_eq_impl $name:ident) => {
+        /// Two values of the same tensor type are equal if their wrapped tensors have the same
+        /// shape and kind, whatever their elements.
+        impl PartialEq for $name {
+            fn eq(&self, other: &Self) -> bool {
+                <Self as $crate::TensorType>::shape_key(self)
+                    == <Self as $crate::TensorType>::shape_key(other)
+            }
+        }
+
+        impl Eq for $name {}
+
+        /// Values are hashed by shape and kind, consistently with PartialEq.
+        impl std::hash::Hash for $name {
+            fn hash<__H: std::hash::Hasher>(&self, state: &mut __H) {
+                std::hash::Hash::hash(&<Self as $crate::TensorType>::shape_key(self), state);
+            }
+        }
+    };
+
+    // The functions generated only for types with an optional leading dimension.
+    (@optional_batch_impl $name:ident, $params:ty, optional_batch [$($dim:tt)*]) => {
+        impl $name {
+            /// The ensure_batched() function returns the value with its optional leading
+            /// dimension present, inserting it with size one if it's absent. The result is
+            /// checked against the full shape, so a ShapeMismatch error is returned for an
+            /// unbatched tensor unless the leading field is one.
+            pub fn ensure_batched(&self, params: &<Self as $crate::TensorType>::InnerType) -> Result<Self, $crate::TensorTypeError> {
+                let expected_size = <Self as $crate::TensorType>::expected_size(params)?;
+                if self.tensor.dim() + 1 == expected_size.len() {
+                    <Self as $crate::TensorType>::new(self.tensor.unsqueeze(0), params)
+                } else {
+                    <Self as $crate::TensorType>::clone(self, params)
+                }
+            }
+        }
+    };
+    (@optional_batch_impl $name:ident, $params:ty, $($shape:tt)*) => {};
+
+    // The value of an option. Options that are checked only when given are Options.
+    (@option_value requires_grad $val:expr) => { Some($val) };
+    (@option_value $opt:ident $val:expr) => { $val };
+
+    // The type definition shared by all forms of the macro. The visibility of the tensor field is
+    // given as `(pub)` or `()`, and the shape as the tokens of one of the @check_shape forms above.
+    (@impl $name:ident, ($($vis:tt)*), ($($shape:tt)*), $params:ty, ($($kinds:tt)*), {$($opt:ident = $val:expr),*}) => {
 
         pub struct $name {
-            pub tensor: tch::Tensor,
+            $($vis)* tensor: tch::Tensor,
+            kind_history: $crate::KindHistory,
         }
 
         impl $crate::TensorType for $name {
             type InnerType = $params;
 
+            const NAME: &'static str = stringify!($name);
+
+            const KINDS: &'static [tch::Kind] = $crate::tensor_type!(@kinds_const $($kinds)*);
+
+            #[allow(clippy::needless_update)]
+            const OPTIONS: $crate::TensorTypeOptions = $crate::TensorTypeOptions {
+                $($opt: $crate::tensor_type!(@option_value $opt $val),)*
+                ..$crate::TensorTypeOptions::DEFAULT
+            };
+
             /// The new() function creates a new wrapper for a tensor. Its input is a tensor
             /// that will be checked for the required shape and a a parameters instance that
             /// contains values for the expected shape. It returns an instance of the new type
@@ -52,33 +1932,64 @@ macro_rules! tensor_type {
             /// assert_eq!(wrapper.tensor().size(), &[2, 3]);
             /// assert_eq!((*wrapper).size(), &[2, 3]);
             /// ```
-            fn new(tensor: tch::Tensor, params: &$params) -> Result<Self, $crate::TensorTypeError> {
-                let tensor_size = tensor.size();
-                let expected_size: Vec<i64> = vec![$(params.$field.into()),*];
-
-                if tensor_size != expected_size {
-                    return Err($crate::TensorTypeError::ShapeMismatch {
-                        type_name: stringify!($name).to_string(),
-                        expected: expected_size,
-                        found: tensor_size
-                    });
+            fn new(tensor: tch::Tensor, params: &Self::InnerType) -> Result<Self, $crate::TensorTypeError> {
+                if cfg!(debug_assertions) || !$crate::UNCHECKED_RELEASE {
+                    Self::check(&tensor, params)?;
                 }
+                $crate::trace_new(stringify!($name), &tensor);
+                Ok(Self { kind_history: $crate::KindHistory::new(&tensor), tensor })
+            }
 
-                if tensor.kind() != $kind {
-                    return Err($crate::TensorTypeError::KindMismatch {
-                        type_name: stringify!($name).to_string(),
-                        expected: $kind,
-                        found: tensor.kind()
-                    });
-                }
-                Ok(Self { tensor })
+            /// The new_ref() function makes the checks of new() on a borrowed tensor, returning a
+            /// wrapper that borrows it.
+            fn new_ref<'a>(
+                tensor: &'a tch::Tensor,
+                params: &Self::InnerType,
+            ) -> Result<$crate::TensorRef<'a, Self>, $crate::TensorTypeError> {
+                Self::check(tensor, params)?;
+                Ok($crate::TensorRef::new(tensor))
+            }
+
+            /// The verify() function re-checks the wrapped tensor against the parameters, making
+            /// the same shape and kind checks as new(). Use it to restore confidence in the type
+            /// after modifying the tensor in place through tensor_mut() with the Warn action, or
+            /// through the field, if declared pub with #[public_tensor].
+            fn verify(&self, params: &Self::InnerType) -> Result<(), $crate::TensorTypeError> {
+                Self::check(&self.tensor, params)
+            }
+
+            /// The check_shape() function makes the shape checks of new() on a tensor without
+            /// wrapping it.
+            fn check_shape(tensor: &tch::Tensor, params: &Self::InnerType) -> Result<(), $crate::TensorTypeError> {
+                let expected_size = || <Self as $crate::TensorType>::expected_size(params);
+                $crate::tensor_type!(@check_shape $name, tensor, expected_size, $($shape)*);
+                Ok(())
+            }
+
+            /// The new_unchecked() function wraps a tensor without checking its shape or kind. It
+            /// skips the size() and kind() calls made by new(), so it's intended for hot paths
+            /// where the caller has already verified the tensor. With the `cast-history` feature,
+            /// kind() is still called once, to start the kind history. Wrapping a tensor of the
+            /// wrong shape or kind breaks the guarantees of the type, so prefer new() elsewhere.
+            fn new_unchecked(tensor: tch::Tensor) -> Self {
+                Self { kind_history: $crate::KindHistory::new(&tensor), tensor }
             }
 
             /// The tensor() function returns a reference to the wrapped tensor.
             fn tensor(&self) -> &tch::Tensor { &self.tensor }
 
-            /// The tensor_mut() function returns a mutable reference to the wrapped tensor.
-            fn tensor_mut(&mut self) -> &mut tch::Tensor { &mut self.tensor }
+            /// The tensor_mut() function returns a guard that dereferences to the wrapped tensor.
+            /// The tensor is re-checked when the guard is dropped.
+            /// Example:
+            ///   my_tensor.tensor_mut(&params).fill_(0.0);
+            fn tensor_mut<'a>(&'a mut self, params: &'a Self::InnerType) -> $crate::TensorMut<'a, Self::InnerType> {
+                $crate::TensorMut::new(
+                    &mut self.tensor,
+                    params,
+                    Self::check,
+                    <Self as $crate::TensorType>::OPTIONS.on_invalid_mut,
+                )
+            }
 
 
             /// The apply_fn() function will apply a given function to the current value held by the
@@ -86,35 +1997,336 @@ macro_rules! tensor_type {
             /// closure that operates on a tch::Tensor and returns a tch::Tensor.
             /// Example:
             ///   let newAB_x2 = newAB.apply_fn(|t: &Tensor| t * 2, &params)?;
-            fn apply_fn<F>(&self, tfn: F, params: &$params) -> Result<Self, $crate::TensorTypeError>
+            fn apply_fn<F>(&self, tfn: F, params: &Self::InnerType) -> Result<Self, $crate::TensorTypeError>
             where
                 F: FnOnce(&tch::Tensor) -> tch::Tensor,
             {
                 let transformed_tensor = tfn(&self.tensor);
-                Self::new(transformed_tensor, params)
+                let mut transformed = Self::new(transformed_tensor, params)?;
+                transformed.kind_history = $crate::KindHistory::extended(
+                    self.kind_history.kinds(),
+                    &transformed.tensor,
+                );
+                Ok(transformed)
             }
 
             /// Note: cloning the tensor type creates a shallow clone of the underlying tensor.
             /// This is potentially confusing because tensor.clone() returns a deep clone.
             /// However, the newtype is a wrapper around a tensor, so cloning the newtype should
             /// clone the wrapper, not the data.
-            fn clone(&self, params: &$params) -> Result<Self, $crate::TensorTypeError> {
-                Self::new(self.tensor.shallow_clone(), params)
+            fn clone(&self, params: &Self::InnerType) -> Result<Self, $crate::TensorTypeError> {
+                let mut cloned = Self::new(self.tensor.shallow_clone(), params)?;
+                cloned.kind_history = self.kind_history.clone();
+                Ok(cloned)
+            }
+
+            fn kind_history(&self) -> &[tch::Kind] {
+                self.kind_history.kinds()
+            }
+
+            fn extend_kind_history(&mut self, previous: &[tch::Kind]) {
+                self.kind_history = $crate::KindHistory::extended(previous, &self.tensor);
             }
 
             /// Unwrap the underlying tch::Tensor.
             fn into_inner(self) -> tch::Tensor {
                 self.tensor
             }
+
+            /// The creation_kind() function returns the kind used when creating tensors of this
+            /// type, given the parameters.
+            fn creation_kind(params: &Self::InnerType) -> Result<tch::Kind, $crate::TensorTypeError> {
+                $crate::tensor_type!(@creation_kind $name, params, $($kinds)*)
+            }
+
+            /// The expected_size() function returns the dimensions a tensor of this type must
+            /// have, given the parameters.
+            fn expected_size(params: &Self::InnerType) -> Result<Vec<i64>, $crate::TensorTypeError> {
+                $crate::tensor_type!(@expected_size $name, params, $($shape)*)
+            }
+        }
+
+        impl $name {
+            /// The check() function makes the checks of new() and verify() on a borrowed tensor,
+            /// without wrapping or cloning it. Use it to ask whether a tensor would be accepted,
+            /// getting the error that new() would return if not.
+            pub fn check(tensor: &tch::Tensor, params: &<Self as $crate::TensorType>::InnerType) -> Result<(), $crate::TensorTypeError> {
+                let allowed_kinds = $crate::tensor_type!(@allowed_kinds params, $($kinds)*);
+                Self::__check_with(
+                    tensor,
+                    || <Self as $crate::TensorType>::expected_size(params),
+                    Some(&allowed_kinds),
+                )
+            }
+
+            // The shape, kind, and option checks, given a function returning the expected size and
+            // the allowed kinds. The kind isn't checked if the allowed kinds are None, and an
+            // EmptyKindSet error is returned if they're an empty set. Every check is made,
+            // so a tensor failing several of them gets a Multiple error listing each failure. The
+            // name is prefixed so that it can't clash with a method defined on the type by its user.
+            #[doc(hidden)]
+            fn __check_with<__F>(
+                tensor: &tch::Tensor,
+                expected_size: __F,
+                allowed_kinds: Option<&[tch::Kind]>,
+            ) -> Result<(), $crate::TensorTypeError>
+            where
+                __F: FnOnce() -> Result<Vec<i64>, $crate::TensorTypeError>,
+            {
+                let mut errors = Vec::new();
+
+                let check_shape = || -> Result<(), $crate::TensorTypeError> {
+                    $crate::tensor_type!(@check_shape $name, tensor, expected_size, $($shape)*);
+                    Ok(())
+                };
+                if let Err(error) = check_shape() {
+                    errors.push(error);
+                }
+
+                match allowed_kinds {
+                    Some([]) => errors.push($crate::TensorTypeError::EmptyKindSet {
+                        type_name: stringify!($name).to_string(),
+                    }),
+                    Some(allowed_kinds) if !allowed_kinds.contains(&tensor.kind()) => {
+                        errors.push($crate::TensorTypeError::KindMismatch {
+                            type_name: stringify!($name).to_string(),
+                            expected: allowed_kinds.to_vec(),
+                            found: tensor.kind()
+                        });
+                    }
+                    _ => {}
+                }
+
+                if <Self as $crate::TensorType>::OPTIONS.contiguous && !tensor.is_contiguous() {
+                    errors.push($crate::TensorTypeError::NotContiguous {
+                        type_name: stringify!($name).to_string(),
+                    });
+                }
+
+                if let Some(requires_grad) = <Self as $crate::TensorType>::OPTIONS.requires_grad {
+                    if tensor.requires_grad() != requires_grad {
+                        errors.push($crate::TensorTypeError::GradMismatch {
+                            type_name: stringify!($name).to_string(),
+                            expected: requires_grad,
+                            found: tensor.requires_grad(),
+                        });
+                    }
+                }
+
+                match errors.len() {
+                    0 => Ok(()),
+                    1 => Err(errors.remove(0)),
+                    _ => Err($crate::TensorTypeError::Multiple(errors)),
+                }
+            }
+
+            // Wrap a tensor created for the type, setting requires_grad if the type requires it.
+            // The name is prefixed like __check_with().
+            #[doc(hidden)]
+            fn __created(tensor: tch::Tensor) -> Self {
+                let tensor = match <Self as $crate::TensorType>::OPTIONS.requires_grad {
+                    Some(true) => tensor.set_requires_grad(true),
+                    _ => tensor,
+                };
+                Self { kind_history: $crate::KindHistory::new(&tensor), tensor }
+            }
+
+            /// The set_requires_grad() function sets whether the wrapped tensor requires
+            /// gradients. Setting a value that differs from the requires_grad option of the type
+            /// makes later checks of the tensor fail.
+            pub fn set_requires_grad(&mut self, requires_grad: bool) {
+                self.tensor = self.tensor.set_requires_grad(requires_grad);
+            }
+
+            /// The new_from_map() function is like new(), but looks up the value of each declared
+            /// field by name in a map, such as one parsed from a configuration file. A
+            /// MissingDimension error is returned if a field isn't in the map. The kind isn't
+            /// checked for types whose kind is given by the parameters.
+            pub fn new_from_map(
+                tensor: tch::Tensor,
+                map: &std::collections::HashMap<String, i64>,
+            ) -> Result<Self, $crate::TensorTypeError> {
+                Self::__check_with(
+                    &tensor,
+                    || $crate::tensor_type!(@expected_size_from_map $name, map, $($shape)*),
+                    Some(<Self as $crate::TensorType>::KINDS).filter(|kinds| !kinds.is_empty()),
+                )?;
+                Ok(Self { kind_history: $crate::KindHistory::new(&tensor), tensor })
+            }
+
+            /// The zeros() function creates an instance holding a tensor of zeros with the
+            /// expected size and the creation kind, on the given device. The tensor matches
+            /// the type by construction, so it is wrapped without being checked. It requires
+            /// gradients if the type's requires_grad option is true.
+            pub fn zeros(params: &<Self as $crate::TensorType>::InnerType, device: tch::Device) -> Result<Self, $crate::TensorTypeError> {
+                let size = <Self as $crate::TensorType>::expected_size(params)?;
+                let kind = <Self as $crate::TensorType>::creation_kind(params)?;
+                Ok(Self::__created(tch::Tensor::zeros(size, (kind, device))))
+            }
+
+            /// The ones() function is like zeros(), but the tensor is filled with ones.
+            pub fn ones(params: &<Self as $crate::TensorType>::InnerType, device: tch::Device) -> Result<Self, $crate::TensorTypeError> {
+                let size = <Self as $crate::TensorType>::expected_size(params)?;
+                let kind = <Self as $crate::TensorType>::creation_kind(params)?;
+                Ok(Self::__created(tch::Tensor::ones(size, (kind, device))))
+            }
+
+            /// The randn() function is like zeros(), but the tensor is filled with values drawn
+            /// from the standard normal distribution. The creation kind must be a floating point
+            /// kind.
+            pub fn randn(params: &<Self as $crate::TensorType>::InnerType, device: tch::Device) -> Result<Self, $crate::TensorTypeError> {
+                let size = <Self as $crate::TensorType>::expected_size(params)?;
+                let kind = <Self as $crate::TensorType>::creation_kind(params)?;
+                Ok(Self::__created(tch::Tensor::randn(size, (kind, device))))
+            }
+
+            /// The from_flat_slice() function creates an instance from a flat slice of values,
+            /// such as a buffer loaded from a file, reshaping it to the expected size and
+            /// converting it to the creation kind. A NumelMismatch error is returned if the
+            /// length of the slice isn't the expected number of elements. The tensor is on the
+            /// CPU.
+            pub fn from_flat_slice<T: tch::kind::Element>(
+                data: &[T],
+                params: &<Self as $crate::TensorType>::InnerType,
+            ) -> Result<Self, $crate::TensorTypeError> {
+                let size = <Self as $crate::TensorType>::expected_size(params)?;
+                let expected = <Self as $crate::TensorType>::expected_numel(params)?;
+                if data.len() as i64 != expected {
+                    return Err($crate::TensorTypeError::NumelMismatch {
+                        type_name: stringify!($name).to_string(),
+                        expected,
+                        found: data.len() as i64,
+                    });
+                }
+                let kind = <Self as $crate::TensorType>::creation_kind(params)?;
+                Ok(Self::__created(tch::Tensor::from_slice(data).reshape(size).to_kind(kind)))
+            }
+
+            /// The shape_signature() function describes the expected shape and kinds for logs and
+            /// diagnostics, naming each dimension with the field that gives it, as in
+            /// `MyTensor[batch_size=1, seq_len=2]:Float`. A dimension is given by its name alone if
+            /// the parameters don't give valid dimensions.
+            pub fn shape_signature(params: &<Self as $crate::TensorType>::InnerType) -> String {
+                let names: Vec<String> = $crate::tensor_type!(@dim_names $($shape)*);
+                let dims: Vec<String> = match <Self as $crate::TensorType>::expected_size(params) {
+                    Ok(size) => size
+                        .iter()
+                        .enumerate()
+                        .map(|(axis, value)| match names.get(axis) {
+                            Some(name) => format!("{name}={value}"),
+                            None => value.to_string(),
+                        })
+                        .collect(),
+                    Err(_) => names,
+                };
+                let kinds: Vec<String> = $crate::tensor_type!(@allowed_kinds params, $($kinds)*)
+                    .iter()
+                    .map(|kind| format!("{kind:?}"))
+                    .collect();
+                format!("{}[{}]:{}", stringify!($name), dims.join(", "), kinds.join("|"))
+            }
+
+            /// The save() function writes the wrapped tensor to a file in the format of
+            /// tch::Tensor::save(). An Io error is returned if the file can't be written.
+            pub fn save(&self, path: &std::path::Path) -> Result<(), $crate::TensorTypeError> {
+                self.tensor.save(path).map_err(|source| $crate::TensorTypeError::Io {
+                    type_name: stringify!($name).to_string(),
+                    source,
+                })
+            }
+
+            /// The load() function reads a tensor written by save(), checking it like new(). An
+            /// Io error is returned if the file can't be read.
+            pub fn load(path: &std::path::Path, params: &<Self as $crate::TensorType>::InnerType) -> Result<Self, $crate::TensorTypeError> {
+                let tensor = tch::Tensor::load(path).map_err(|source| $crate::TensorTypeError::Io {
+                    type_name: stringify!($name).to_string(),
+                    source,
+                })?;
+                <Self as $crate::TensorType>::new(tensor, params)
+            }
         }
 
 
+        $crate::tensor_type!(@optional_batch_impl $name, $params, $($shape)*);
+        $crate::tensor_type!(@expected_kind_impl $name, $($kinds)*);
+
         impl std::fmt::Debug for $name {
             fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
                 write!(f, "{:?}({:?})", stringify!($name), self.tensor)
             }
         }
 
+        /// Display gives a concise description of the wrapped tensor without its contents, such
+        /// as `MyTensor[1, 2, 3] (Float, Cpu)`.
+        impl std::fmt::Display for $name {
+            fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+                write!(
+                    f,
+                    "{}{:?} ({:?}, {:?})",
+                    stringify!($name),
+                    self.tensor.size(),
+                    self.tensor.kind(),
+                    self.tensor.device()
+                )
+            }
+        }
+
+        /// Implementing TryFrom allows a tensor to be wrapped with `try_from()` or `try_into()`
+        /// when the parameters type implements Default, checking it against the default
+        /// parameters. The bound is expressed through the InnerType so that types whose
+        /// parameters don't implement Default simply don't get the impl.
+        impl<__P: Default> std::convert::TryFrom<tch::Tensor> for $name
+        where
+            $name: $crate::TensorType<InnerType = __P>,
+        {
+            type Error = $crate::TensorTypeError;
+
+            fn try_from(tensor: tch::Tensor) -> Result<Self, Self::Error> {
+                <$name as $crate::TensorType>::new(tensor, &__P::default())
+            }
+        }
+
+        /// Implementing Default gives a tensor of zeros with the size and creation kind given by
+        /// the default parameters, on the CPU, when the parameters type implements Default. It
+        /// panics if the default parameters don't give valid dimensions, for example if a
+        /// dimension is zero.
+        impl<__P: Default> Default for $name
+        where
+            $name: $crate::TensorType<InnerType = __P>,
+        {
+            fn default() -> Self {
+                let params = __P::default();
+                let size = <$name as $crate::TensorType>::expected_size(&params)
+                    .expect("the default parameters give the dimensions of the tensor type");
+                let kind = <$name as $crate::TensorType>::creation_kind(&params)
+                    .expect("the default parameters give the kind of the tensor type");
+                Self::__created(tch::Tensor::zeros(size, (kind, tch::Device::Cpu)))
+            }
+        }
+
+        /// Serialize writes the kind, shape, and elements of the wrapped tensor.
+        impl $crate::serde::Serialize for $name {
+            fn serialize<S>(&self, serializer: S) -> Result<S::Ok, S::Error>
+            where
+                S: $crate::serde::Serializer,
+            {
+                $crate::serialize_tensor(&self.tensor, serializer)
+            }
+        }
+
+        /// Deserialize restores the tensor without checking it, because the parameters aren't
+        /// available: the value is wrapped with new_unchecked(), so it may have any shape or
+        /// kind. Call verify() on the result, or deserialize with seed() instead.
+        impl<'de> $crate::serde::Deserialize<'de> for $name {
+            fn deserialize<D>(deserializer: D) -> Result<Self, D::Error>
+            where
+                D: $crate::serde::Deserializer<'de>,
+            {
+                $crate::deserialize_tensor(deserializer)
+                    .map(<Self as $crate::TensorType>::new_unchecked)
+            }
+        }
+
         /// Implementing Deref allows the wrapped tch::Tensor to be dereferenced.
         impl std::ops::Deref for $name {
             type Target = tch::Tensor;
@@ -124,23 +2336,591 @@ macro_rules! tensor_type {
             }
         }
 
+        /// AsRef and Borrow allow the value to be passed to generic code written against the
+        /// wrapped tch::Tensor.
+        impl AsRef<tch::Tensor> for $name {
+            fn as_ref(&self) -> &tch::Tensor {
+                &self.tensor
+            }
+        }
+
+        impl std::borrow::Borrow<tch::Tensor> for $name {
+            fn borrow(&self) -> &tch::Tensor {
+                &self.tensor
+            }
+        }
+
+        /// Converting into a tch::Tensor moves out the wrapped tensor, as into_inner() does.
+        impl From<$name> for tch::Tensor {
+            fn from(value: $name) -> Self {
+                value.tensor
+            }
+        }
+
+        // Values of the same tensor type can be added, subtracted, multiplied, and divided
+        // element-wise, giving the same type. Mixing different tensor types won't compile. The
+        // operands must have matching shapes; use checked_add() etc. to get an error rather than
+        // a panic.
+        $crate::tensor_type!(@binary_ops $name, $($shape)*);
+    };
+
+    // The kind of the tensor, following the shape. Each form may be followed by options as
+    // `name = value` pairs, e.g. `broadcast = true`. See TensorTypeOptions for the available
+    // options.
+    //
+    // The kind given by a field of the parameters, e.g. kind = compute_kind, where the field is a
+    // tch::Kind. This allows one type to be used at a precision chosen at runtime.
+    (@kinds $name:ident, $vis:tt, $shape:tt, $params:ty, kind = $field:ident $(, $opt:ident = $val:expr)*) => {
+        $crate::tensor_type!(@impl $name, $vis, $shape, $params, (param $field), {$($opt = $val),*});
+    };
+    // The set of kinds given by a field of the parameters, e.g. kinds = allowed_kinds, where the
+    // field is a Vec<tch::Kind> or slice. This allows the accepted kinds to come from the runtime
+    // configuration. The first kind in the set is used to create tensors.
+    (@kinds $name:ident, $vis:tt, $shape:tt, $params:ty, kinds = $field:ident $(, $opt:ident = $val:expr)*) => {
+        $crate::tensor_type!(@impl $name, $vis, $shape, $params, (param_set $field), {$($opt = $val),*});
+    };
+    // A category of kinds, e.g. KindClass::AnyFloat. The tensor is accepted if its kind is any
+    // member of the category.
+    (@kinds $name:ident, $vis:tt, $shape:tt, $params:ty, KindClass::$class:ident $(, $opt:ident = $val:expr)*) => {
+        $crate::tensor_type!(@impl $name, $vis, $shape, $params, (class $class), {$($opt = $val),*});
+    };
+    // A set of acceptable kinds, e.g. [Kind::Float, Kind::Double]. The tensor is accepted if its
+    // kind is any member of the set.
+    (@kinds $name:ident, $vis:tt, $shape:tt, $params:ty, [$($kind:expr),+ $(,)?] $(, $opt:ident = $val:expr)*) => {
+        $crate::tensor_type!(@impl $name, $vis, $shape, $params, (const [$($kind),+]), {$($opt = $val),*});
+    };
+    // A single kind, e.g. Kind::Float.
+    (@kinds $name:ident, $vis:tt, $shape:tt, $params:ty, $kind:expr $(, $opt:ident = $val:expr)*) => {
+        $crate::tensor_type!(@impl $name, $vis, $shape, $params, (const [$kind]), {$($opt = $val),*});
+    };
+
+    // Dimensions given at runtime, e.g. tensor_type!(DynTensor, dynamic, Kind::Float). There's no
+    // parameters type: new() and the other functions taking parameters take the expected
+    // dimensions as a slice, e.g. DynTensor::new(tensor, &[2, 3]).
+    (@shape $name:ident, $vis:tt, dynamic, $($rest:tt)+) => {
+        $crate::tensor_type!(@kinds $name, $vis, (dynamic), [i64], $($rest)+);
+    };
+
+    // Rank-only checking, e.g. rank = 3. Only the number of dimensions is checked, not their sizes.
+    (@shape $name:ident, $vis:tt, rank = $rank:expr, $params:ty, $($rest:tt)+) => {
+        $crate::tensor_type!(@kinds $name, $vis, (rank = $rank), $params, $($rest)+);
+    };
+
+    // Floating point fields, e.g. rounded [batch_size, sequence_length], where the fields are f32
+    // or f64. Each dimension is rounded to the nearest integer.
+    (@shape $name:ident, $vis:tt, rounded [$($dim:tt)*], $params:ty, $($rest:tt)+) => {
+        $crate::tensor_type!(@kinds $name, $vis, (rounded [$($dim)*]), $params, $($rest)+);
+    };
+
+    // Getter methods of the parameters, e.g. getters [batch_size, sequence_length], which calls
+    // params.batch_size() and params.sequence_length(). This allows the fields of the parameters
+    // to be private. The methods may return any type convertible into i64.
+    (@shape $name:ident, $vis:tt, getters [$($dim:tt)*], $params:ty, $($rest:tt)+) => {
+        $crate::tensor_type!(@kinds $name, $vis, (getters [$($dim)*]), $params, $($rest)+);
+    };
+
+    // Getter methods written as calls, e.g. [batch_size(), sequence_length()], the same as
+    // getters [batch_size, sequence_length]. Use the getters form for sums and products.
+    (@shape $name:ident, $vis:tt, [$($field:ident ()),+ $(,)?], $params:ty, $($rest:tt)+) => {
+        $crate::tensor_type!(@kinds $name, $vis, (getters [$($field),+]), $params, $($rest)+);
+    };
+
+    // An optional leading dimension, e.g. [batch_size?, sequence_length, d_model]. Tensors are
+    // accepted with or without it, such as batched and single examples.
+    (@shape $name:ident, $vis:tt, [$batch:ident ?, $($dim:tt)*], $params:ty, $($rest:tt)+) => {
+        $crate::tensor_type!(@kinds $name, $vis, (optional_batch [$batch, $($dim)*]), $params, $($rest)+);
+    };
+
+    // Integer fields, e.g. [batch_size, sequence_length].
+    (@shape $name:ident, $vis:tt, [$($dim:tt)*], $params:ty, $($rest:tt)+) => {
+        $crate::tensor_type!(@kinds $name, $vis, ([$($dim)*]), $params, $($rest)+);
+    };
+
+    // The wrapped tensor is a private field, so that it can't be replaced with a tensor of the
+    // wrong shape from outside the module defining the type. Code that relies on the field being
+    // public can keep it so by marking the type with #[public_tensor], e.g.
+    // tensor_type!(#[public_tensor] MyTensor, [rows], Params, Kind::Float).
+    (#[public_tensor] $name:ident, $($rest:tt)+) => {
+        $crate::tensor_type!(@shape $name, (pub), $($rest)+);
+    };
+
+    // PartialEq, comparing the wrapped tensors, is implemented for types marked with
+    // #[partial_eq], e.g. tensor_type!(#[partial_eq] MyTensor, [rows], Params, Kind::Float). The
+    // markers may be given in either order.
+    (#[partial_eq] $(#[$marker:ident])* $name:ident, $($rest:tt)+) => {
+        $crate::tensor_type!($(#[$marker])* $name, $($rest)+);
+        $crate::tensor_type!(@partial_eq_impl $name);
+    };
+    (#[public_tensor] #[partial_eq] $name:ident, $($rest:tt)+) => {
+        $crate::tensor_type!(#[partial_eq] #[public_tensor] $name, $($rest)+);
+    };
+
+    // PartialEq, Eq, and Hash by shape and kind are implemented for types marked with #[shape_eq],
+    // e.g. tensor_type!(#[shape_eq] MyTensor, [rows], Params, Kind::Float). It can't be combined
+    // with #[partial_eq].
+    (#[shape_eq] $(#[$marker:ident])* $name:ident, $($rest:tt)+) => {
+        $crate::tensor_type!($(#[$marker])* $name, $($rest)+);
+        $crate::tensor_type!(@shape_eq_impl $name);
+    };
+    (#[public_tensor] #[shape_eq] $name:ident, $($rest:tt)+) => {
+        $crate::tensor_type!(#[shape_eq] #[public_tensor] $name, $($rest)+);
+    };
+    ($name:ident, $($rest:tt)+) => {
+        $crate::tensor_type!(@shape $name, (), $($rest)+);
     };
 }
 
-#[derive(thiserror::Error, Debug)]
+/// The errors returned when a tensor doesn't match its tensor type. Display and Error are written
+/// by hand rather than derived, to keep the crate's dependencies small.
+#[derive(Debug)]
 pub enum TensorTypeError {
-    #[error("shape mismatch on TensorType {type_name:?}: expected dimensions {expected:?}, found {found:?}")]
     ShapeMismatch {
         type_name: String,
+        dim_names: Vec<String>,
         expected: Vec<i64>,
         found: Vec<i64>,
     },
-    #[error(
-        "kind mismatch on TensorType {type_name:?}: expected kind {expected:?}, found {found:?}"
-    )]
     KindMismatch {
         type_name: String,
-        expected: tch::Kind,
+        expected: Vec<tch::Kind>,
         found: tch::Kind,
     },
+    RankMismatch {
+        type_name: String,
+        expected_rank: usize,
+        found_rank: usize,
+    },
+    NumelMismatch {
+        type_name: String,
+        expected: i64,
+        found: i64,
+    },
+    UnknownDimensions {
+        type_name: String,
+    },
+    MissingDimension {
+        type_name: String,
+        name: String,
+    },
+    GradMismatch {
+        type_name: String,
+        expected: bool,
+        found: bool,
+    },
+    NonIntegralDimension {
+        type_name: String,
+        axis: usize,
+        value: f64,
+    },
+    IndivisibleDimension {
+        type_name: String,
+        axis: usize,
+        size: i64,
+        chunks: i64,
+    },
+    InvalidDimension {
+        type_name: String,
+        axis: usize,
+        value: i64,
+    },
+    AxisMismatch {
+        type_name: String,
+        axis: usize,
+        expected: i64,
+        found: i64,
+    },
+    InvalidAxis {
+        type_name: String,
+        axis: i64,
+        rank: usize,
+    },
+    IndexOutOfRange {
+        type_name: String,
+        axis: usize,
+        index: i64,
+        size: i64,
+    },
+    DimensionOverflow {
+        type_name: String,
+        value: String,
+    },
+    NotContiguous {
+        type_name: String,
+    },
+    DimensionOutOfRange {
+        type_name: String,
+        axis: usize,
+        value: i64,
+        min: i64,
+        max: i64,
+    },
+    NegativeParameter {
+        type_name: String,
+        value: String,
+    },
+    Io {
+        type_name: String,
+        source: tch::TchError,
+    },
+    DeviceMismatch {
+        type_name: String,
+        expected: tch::Device,
+        found: tch::Device,
+    },
+    EmptyKindSet {
+        type_name: String,
+    },
+    EmptyItems {
+        type_name: String,
+    },
+    /// An operation on the wrapped tensor failed inside libtorch, such as for a dimension out of
+    /// range. The error from tch is given as the source.
+    Tch {
+        type_name: String,
+        source: tch::TchError,
+    },
+    /// Several checks failed on the same tensor, such as both its shape and its kind. Each failure
+    /// is listed, in the order the checks are made, so that all of them can be fixed at once.
+    Multiple(Vec<TensorTypeError>),
+}
+
+impl std::fmt::Display for TensorTypeError {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            TensorTypeError::ShapeMismatch {
+                type_name,
+                dim_names,
+                expected,
+                found,
+            } => write!(
+                f,
+                "shape mismatch on TensorType {type_name:?}: {}; expected dimensions {expected:?}, found {found:?}",
+                describe_mismatched_axis(dim_names, expected, found)
+            ),
+            TensorTypeError::KindMismatch {
+                type_name,
+                expected,
+                found,
+            } => match crate::KindClass::of(expected) {
+                Some(class) => write!(
+                    f,
+                    "kind mismatch on TensorType {type_name:?}: expected {class} {expected:?}, found {found:?}"
+                ),
+                None => write!(
+                    f,
+                    "kind mismatch on TensorType {type_name:?}: expected kinds {expected:?}, found {found:?}"
+                ),
+            },
+            TensorTypeError::RankMismatch {
+                type_name,
+                expected_rank,
+                found_rank,
+            } => write!(
+                f,
+                "rank mismatch on TensorType {type_name:?}: expected rank {expected_rank}, found rank {found_rank}"
+            ),
+            TensorTypeError::NumelMismatch {
+                type_name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "element count mismatch on TensorType {type_name:?}: expected {expected} elements, found {found}"
+            ),
+            TensorTypeError::UnknownDimensions { type_name } => write!(
+                f,
+                "dimensions of TensorType {type_name:?} are unknown because they aren't declared by the type"
+            ),
+            TensorTypeError::MissingDimension { type_name, name } => write!(
+                f,
+                "missing dimension on TensorType {type_name:?}: no value for {name:?}"
+            ),
+            TensorTypeError::GradMismatch {
+                type_name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "requires_grad mismatch on TensorType {type_name:?}: expected {expected}, found {found}"
+            ),
+            TensorTypeError::NonIntegralDimension {
+                type_name,
+                axis,
+                value,
+            } => write!(
+                f,
+                "non-integral dimension on TensorType {type_name:?}: axis {axis} has size {value}, but dimensions must be whole numbers"
+            ),
+            TensorTypeError::IndivisibleDimension {
+                type_name,
+                axis,
+                size,
+                chunks,
+            } => write!(
+                f,
+                "indivisible dimension on TensorType {type_name:?}: axis {axis} of size {size} can't be split into {chunks} equal chunks"
+            ),
+            TensorTypeError::InvalidDimension {
+                type_name,
+                axis,
+                value,
+            } => write!(
+                f,
+                "invalid dimension on TensorType {type_name:?}: axis {axis} has size {value}, but dimensions must be at least 1"
+            ),
+            TensorTypeError::AxisMismatch {
+                type_name,
+                axis,
+                expected,
+                found,
+            } => write!(
+                f,
+                "axis mismatch on TensorType {type_name:?}: axis {axis} has size {found}, but the other tensor's axis has size {expected}"
+            ),
+            TensorTypeError::InvalidAxis {
+                type_name,
+                axis,
+                rank,
+            } => write!(
+                f,
+                "invalid axis on TensorType {type_name:?}: axis {axis} is out of range for a tensor of rank {rank}"
+            ),
+            TensorTypeError::IndexOutOfRange {
+                type_name,
+                axis,
+                index,
+                size,
+            } => write!(
+                f,
+                "index out of range on TensorType {type_name:?}: index {index} is out of range for axis {axis} of size {size}"
+            ),
+            TensorTypeError::DimensionOverflow { type_name, value } => write!(
+                f,
+                "dimension overflow on TensorType {type_name:?}: {value} is out of the range of i64"
+            ),
+            TensorTypeError::NotContiguous { type_name } => write!(
+                f,
+                "non-contiguous tensor on TensorType {type_name:?}: the tensor must be contiguous in memory"
+            ),
+            TensorTypeError::DimensionOutOfRange {
+                type_name,
+                axis,
+                value,
+                min,
+                max,
+            } => write!(
+                f,
+                "dimension out of range on TensorType {type_name:?}: axis {axis} has size {value}, but must be between {min} and {max}"
+            ),
+            TensorTypeError::NegativeParameter { type_name, value } => write!(
+                f,
+                "negative value on parameter type {type_name:?}: {value} is less than 0"
+            ),
+            TensorTypeError::Io { type_name, source } => write!(
+                f,
+                "I/O error on TensorType {type_name:?}: {source}"
+            ),
+            TensorTypeError::DeviceMismatch {
+                type_name,
+                expected,
+                found,
+            } => write!(
+                f,
+                "device mismatch on TensorType {type_name:?}: expected {expected:?}, found {found:?}"
+            ),
+            TensorTypeError::EmptyKindSet { type_name } => write!(
+                f,
+                "empty set of kinds on TensorType {type_name:?}: the parameters must allow at least one kind"
+            ),
+            TensorTypeError::EmptyItems { type_name } => write!(
+                f,
+                "no items on TensorType {type_name:?}: at least one value is required"
+            ),
+            TensorTypeError::Tch { type_name, source } => write!(
+                f,
+                "tch error on TensorType {type_name:?}: {source}"
+            ),
+            TensorTypeError::Multiple(errors) => {
+                write!(f, "{} errors:", errors.len())?;
+                for error in errors {
+                    write!(f, "\n  - {error}")?;
+                }
+                Ok(())
+            }
+        }
+    }
+}
+
+impl TensorTypeError {
+    /// Describe the error over several lines, for reading rather than logging. A ShapeMismatch
+    /// shows the expected and found shapes aligned axis by axis, with a caret under each axis that
+    /// differs:
+    ///
+    /// ```text
+    /// shape mismatch on TensorType "MyTensor":
+    ///   axis      0          1       2
+    ///   name      batch_size seq_len d_model
+    ///   expected  1          2       3
+    ///   found     1          5       3
+    ///                        ^
+    /// ```
+    ///
+    /// The other errors are described as by Display.
+    pub fn detailed(&self) -> String {
+        match self {
+            TensorTypeError::ShapeMismatch {
+                type_name,
+                dim_names,
+                expected,
+                found,
+            } => {
+                let rank = expected.len().max(found.len());
+                let cell = |size: &[i64], axis: usize| {
+                    size.get(axis).map_or("-".to_string(), i64::to_string)
+                };
+                let mut rows: Vec<(&str, Vec<String>)> =
+                    vec![("axis", (0..rank).map(|axis| axis.to_string()).collect())];
+                if !dim_names.is_empty() {
+                    rows.push((
+                        "name",
+                        (0..rank)
+                            .map(|axis| dim_names.get(axis).cloned().unwrap_or_default())
+                            .collect(),
+                    ));
+                }
+                rows.push((
+                    "expected",
+                    (0..rank).map(|axis| cell(expected, axis)).collect(),
+                ));
+                rows.push(("found", (0..rank).map(|axis| cell(found, axis)).collect()));
+                rows.push((
+                    "",
+                    (0..rank)
+                        .map(|axis| {
+                            let differs = expected.get(axis) != found.get(axis);
+                            if differs { "^" } else { "" }.to_string()
+                        })
+                        .collect(),
+                ));
+
+                let widths: Vec<usize> = (0..rank)
+                    .map(|axis| {
+                        rows.iter()
+                            .map(|(_, cells)| cells[axis].len())
+                            .max()
+                            .unwrap_or(0)
+                    })
+                    .collect();
+                let mut out = format!("shape mismatch on TensorType {type_name:?}:");
+                for (label, cells) in &rows {
+                    let line: Vec<String> = cells
+                        .iter()
+                        .zip(&widths)
+                        .map(|(cell, &width)| format!("{cell:width$}"))
+                        .collect();
+                    out.push_str(format!("\n  {label:9} {}", line.join(" ")).trim_end());
+                }
+                out
+            }
+            TensorTypeError::Multiple(errors) => {
+                let details: Vec<String> = errors.iter().map(TensorTypeError::detailed).collect();
+                format!("{} errors:\n{}", errors.len(), details.join("\n"))
+            }
+            _ => self.to_string(),
+        }
+    }
+}
+
+impl std::error::Error for TensorTypeError {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        match self {
+            TensorTypeError::Io { source, .. } | TensorTypeError::Tch { source, .. } => {
+                Some(source)
+            }
+            _ => None,
+        }
+    }
+}
+
+/// A TensorTypeError tagged with the tensor type that returned it, so that the type of a `Result`
+/// says which tensor type failed. Declare a per-type name for it with an alias, e.g.
+/// `type MyTensorError = TypedTensorError<MyTensor>;`. It converts from a TensorTypeError, so `?`
+/// tags errors in functions returning it.
+///
+/// # Example
+/// ```
+/// use tensor_types::{tensor_type, TensorType, TypedTensorError};
+/// use tch::{Device, Kind, Tensor};
+///
+/// pub struct Params {
+///     rows: i64,
+/// }
+/// tensor_type!(Row, [rows], Params, Kind::Float);
+/// type RowError = TypedTensorError<Row>;
+///
+/// fn make_row(t: Tensor, params: &Params) -> Result<Row, RowError> {
+///     Ok(Row::new(t, params)?)
+/// }
+///
+/// let err = make_row(Tensor::zeros([3], (Kind::Float, Device::Cpu)), &Params { rows: 2 });
+/// assert!(err.is_err());
+/// ```
+pub struct TypedTensorError<T> {
+    pub error: TensorTypeError,
+    marker: std::marker::PhantomData<fn() -> T>,
+}
+
+impl<T: TensorType> From<TensorTypeError> for TypedTensorError<T> {
+    fn from(error: TensorTypeError) -> Self {
+        Self {
+            error,
+            marker: std::marker::PhantomData,
+        }
+    }
+}
+
+impl<T: TensorType> std::fmt::Debug for TypedTensorError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        f.debug_struct("TypedTensorError")
+            .field("tensor_type", &T::NAME)
+            .field("error", &self.error)
+            .finish()
+    }
+}
+
+impl<T: TensorType> std::fmt::Display for TypedTensorError<T> {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        self.error.fmt(f)
+    }
+}
+
+impl<T: TensorType> std::error::Error for TypedTensorError<T> {
+    fn source(&self) -> Option<&(dyn std::error::Error + 'static)> {
+        Some(&self.error)
+    }
+}
+
+/// Describe the first axis at which the found dimensions differ from the expected ones, naming it
+/// with the field that gives its size when known.
+fn describe_mismatched_axis(dim_names: &[String], expected: &[i64], found: &[i64]) -> String {
+    if expected.len() != found.len() {
+        return format!(
+            "expected rank {}, found rank {}",
+            expected.len(),
+            found.len()
+        );
+    }
+    match expected.iter().zip(found).position(|(e, f)| e != f) {
+        Some(axis) => match dim_names.get(axis) {
+            Some(name) => format!(
+                "axis '{}' (index {}): expected {}, found {}",
+                name, axis, expected[axis], found[axis]
+            ),
+            None => format!(
+                "axis {}: expected {}, found {}",
+                axis, expected[axis], found[axis]
+            ),
+        },
+        None => "dimensions match".to_string(),
+    }
 }
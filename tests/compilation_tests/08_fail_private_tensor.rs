@@ -0,0 +1,24 @@
+use tensor_types::TensorType;
+
+mod types {
+    use tensor_types::tensor_type;
+
+    pub struct Params {
+        pub my_param1: i64,
+        pub my_param2: i64,
+    }
+    tensor_type!(MyTensor, [my_param1, my_param2], Params, tch::Kind::Double);
+}
+
+fn main() {
+    let params = types::Params {
+        my_param1: 2,
+        my_param2: 2,
+    };
+
+    let tensor = tch::Tensor::zeros([2, 2], (tch::Kind::Double, tch::Device::Cpu));
+    let mut my_tensor = types::MyTensor::new(tensor, &params).unwrap();
+    // This line fails to compile because the wrapped tensor is private to the module defining the
+    // type, so it can't be replaced with a tensor of the wrong shape.
+    my_tensor.tensor = tch::Tensor::zeros([3], (tch::Kind::Double, tch::Device::Cpu));
+}
@@ -1,8 +1,10 @@
 #[cfg(test)]
 mod tests {
-    use tensor_types::parameter_type;
+    use tensor_types::{parameter_type, TensorTypeError};
 
     parameter_type!(TestParamType, i64);
+    parameter_type!(NonNegativeParamType, i64, non_negative);
+    parameter_type!(NonNegativeUnsignedType, u32, non_negative);
 
     #[test]
     fn test_from() {
@@ -17,6 +19,29 @@ mod tests {
         assert_eq!(*new_type_value, value);
     }
 
+    #[test]
+    fn test_get() {
+        const VALUE: TestParamType = TestParamType(3);
+        const INNER: i64 = VALUE.get();
+        assert_eq!(INNER, 3);
+        assert_eq!([0u8; VALUE.get() as usize].len(), 3);
+    }
+
+    #[cfg(feature = "num-traits")]
+    #[test]
+    fn test_num_traits() {
+        use tensor_types::num_traits::{One, Zero};
+
+        fn sum<T: Zero + Copy>(values: &[T]) -> T {
+            values.iter().fold(T::zero(), |total, &value| total + value)
+        }
+        let total = sum(&[TestParamType(2), TestParamType(3)]);
+        assert_eq!(total, TestParamType(5));
+        assert!(TestParamType::zero().is_zero());
+        assert_eq!(TestParamType::one() * total, total);
+        assert_eq!(*NonNegativeParamType::zero(), 0);
+    }
+
     #[test]
     fn test_deref_mut() {
         let mut value = TestParamType(42);
@@ -30,12 +55,83 @@ mod tests {
         assert_eq!(format!("{}", value), "42");
     }
 
+    #[test]
+    fn test_display_separators() {
+        let value = TestParamType::from(1280);
+        if cfg!(feature = "num-format") {
+            assert_eq!(format!("{}", value), "1,280");
+        } else {
+            assert_eq!(format!("{}", value), "1280");
+        }
+    }
+
     #[test]
     fn test_debug() {
         let value = TestParamType::from(42);
         assert_eq!(format!("{:?}", value), "TestParamType(42)");
     }
 
+    #[test]
+    fn test_inner_types() {
+        parameter_type!(SequenceLength, usize);
+        parameter_type!(Channels, u32);
+
+        let length = SequenceLength::from(1280usize);
+        assert_eq!(*length, 1280usize);
+        assert_eq!(i64::try_from(length).unwrap(), 1280i64);
+        #[cfg(feature = "num-format")]
+        assert_eq!(format!("{}", length), "1,280");
+
+        let channels = Channels(3);
+        assert_eq!(channels.as_ref(), &3u32);
+        assert_eq!(i64::from(channels), 3i64);
+    }
+
+    #[test]
+    fn test_oversized_inner_value() {
+        parameter_type!(BigDimension, u64);
+        assert!(matches!(
+            i64::try_from(BigDimension(u64::MAX)),
+            Err(TensorTypeError::DimensionOverflow { .. })
+        ));
+    }
+
+    #[test]
+    fn test_checked_conversions() {
+        parameter_type!(BigDimension, u64);
+        assert_eq!(BigDimension(7).try_to_i64().unwrap(), 7);
+        assert_eq!(BigDimension(u64::MAX).to_i64_saturating(), i64::MAX);
+        match BigDimension(u64::MAX).try_to_i64() {
+            Err(TensorTypeError::DimensionOverflow { type_name, value }) => {
+                assert_eq!(type_name, "BigDimension");
+                assert_eq!(value, u64::MAX.to_string());
+            }
+            _ => panic!("expected DimensionOverflow"),
+        }
+
+        parameter_type!(HugeDimension, i128);
+        assert_eq!(HugeDimension(i128::MIN).to_i64_saturating(), i64::MIN);
+        assert_eq!(HugeDimension(-3).to_i64_saturating(), -3);
+    }
+
+    #[test]
+    fn test_from_str() {
+        let value: TestParamType = "128".parse().unwrap();
+        assert_eq!(*value, 128);
+
+        // Signed inner types accept negative values.
+        let value: TestParamType = "-1".parse().unwrap();
+        assert_eq!(*value, -1);
+
+        let err = "12x".parse::<TestParamType>().unwrap_err();
+        assert_eq!(err.type_name, "TestParamType");
+        assert_eq!(err.value, "12x");
+        assert_eq!(
+            err.to_string(),
+            "invalid value \"12x\" for parameter type TestParamType: invalid digit found in string"
+        );
+    }
+
     #[test]
     fn test_serde_serialize() {
         let value = TestParamType::from(42);
@@ -47,4 +143,24 @@ mod tests {
         let value: TestParamType = serde_json::from_str("42").unwrap();
         assert_eq!(*value, 42);
     }
+
+    #[test]
+    fn test_non_negative() {
+        assert_eq!(*NonNegativeParamType::new(0).unwrap(), 0);
+        assert_eq!(*NonNegativeParamType::try_from(8).unwrap(), 8);
+        assert_eq!(*NonNegativeUnsignedType::new(8).unwrap(), 8);
+        assert_eq!(i64::from(NonNegativeParamType::new(8).unwrap()), 8);
+
+        match NonNegativeParamType::new(-1) {
+            Err(TensorTypeError::NegativeParameter { type_name, value }) => {
+                assert_eq!(type_name, "NonNegativeParamType");
+                assert_eq!(value, "-1");
+            }
+            _ => panic!("expected NegativeParameter"),
+        }
+
+        let value: NonNegativeParamType = serde_json::from_str("42").unwrap();
+        assert_eq!(*value, 42);
+        assert!(serde_json::from_str::<NonNegativeParamType>("-42").is_err());
+    }
 }
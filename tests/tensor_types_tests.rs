@@ -2,7 +2,10 @@
 mod tests {
     use anyhow::Result;
     use tch::{Device, Kind, Tensor};
-    use tensor_types::{parameter_type, tensor_type, TensorType, TensorTypeError};
+    use tensor_types::{
+        assert_shape, parameter_type, tensor_type, tensor_types, ConstTensor, InvalidMutAction,
+        KindClass, TensorType, TensorTypeError, TensorTypeOptions, TypedTensorError,
+    };
 
     // This test shows the basic, correct usage of the parameter_type and tensor_type macros.
     #[test]
@@ -73,6 +76,7 @@ mod tests {
         my_param3: MyParam3,
     }
     tensor_type!(
+        #[partial_eq]
         MyTensor,
         [my_param1, my_param2, my_param3],
         Params,
@@ -95,10 +99,15 @@ mod tests {
         match MyTensor::new(t, &params) {
             Err(TensorTypeError::ShapeMismatch {
                 type_name,
+                dim_names,
                 expected,
                 found,
             }) => {
-                if type_name != "MyTensor" || expected != vec![1, 2, 3] || found != vec![1, 2] {
+                if type_name != "MyTensor"
+                    || dim_names != vec!["my_param1", "my_param2", "my_param3"]
+                    || expected != vec![1, 2, 3]
+                    || found != vec![1, 2]
+                {
                     panic!("expected ShapeMismatch, but unexpected type_name ({}), found ({:?}) or expected ({:?})", type_name, found, expected)
                 }
             }
@@ -110,130 +119,2694 @@ mod tests {
         match MyTensor::new(t, &params) {
             Err(TensorTypeError::ShapeMismatch {
                 type_name,
+                dim_names,
                 expected,
                 found,
             }) => {
-                if type_name != "MyTensor" || expected != vec![1, 2, 3] || found != vec![1, 2, 1] {
+                if type_name != "MyTensor"
+                    || dim_names != vec!["my_param1", "my_param2", "my_param3"]
+                    || expected != vec![1, 2, 3]
+                    || found != vec![1, 2, 1]
+                {
                     panic!("expected ShapeMismatch, but unexpected type_name ({}), found ({:?}) or expected ({:?})", type_name, found, expected)
                 }
             }
             _ => panic!("expected ShapeMismatch"),
         };
+
+        // The error message names the first mismatched axis.
+        let t = Tensor::randn([1, 2, 1], (Kind::Float, Device::Cpu));
+        let err = MyTensor::new(t, &params).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            "shape mismatch on TensorType \"MyTensor\": axis 'my_param3' (index 2): expected 3, \
+             found 1; expected dimensions [1, 2, 3], found [1, 2, 1]"
+        );
+    }
+
+    #[test]
+    fn test_tensor_types() {
+        let params = setup();
+        tensor_types! {
+            Params;
+            FullTensor => [my_param1, my_param2, my_param3]: Kind::Float;
+            RowTensor => [my_param3]: Kind::Int64;
+            BiasTensor => [my_param1, my_param2, my_param3]: Kind::Float, broadcast = true;
+        }
+        let t = Tensor::zeros([1, 2, 3], (Kind::Float, Device::Cpu));
+        assert!(FullTensor::new(t, &params).is_ok());
+        let t = Tensor::zeros([3], (Kind::Int64, Device::Cpu));
+        assert!(RowTensor::new(t, &params).is_ok());
+        let t = Tensor::zeros([3], (Kind::Float, Device::Cpu));
+        assert!(BiasTensor::new(t, &params).is_ok());
+    }
+
+    #[test]
+    fn test_tensor_types_kind_forms() {
+        let params = setup();
+        tensor_types! {
+            Params;
+            SetTensor => [my_param2, my_param3]: [Kind::Float, Kind::Double];
+            ClassTensor => [my_param2, my_param3]: KindClass::AnyInt, broadcast = true;
+            #[public_tensor] PublicTensor => [my_param2, my_param3]: Kind::Float
+        }
+        let t = Tensor::zeros([2, 3], (Kind::Double, Device::Cpu));
+        assert!(SetTensor::new(t, &params).is_ok());
+        let t = Tensor::zeros([3], (Kind::Int8, Device::Cpu));
+        assert!(ClassTensor::new(t, &params).is_ok());
+        let t = Tensor::zeros([2, 3], (Kind::Float, Device::Cpu));
+        let public = PublicTensor::new(t, &params).unwrap();
+        assert_eq!(public.tensor.size(), &[2, 3]);
+
+        pub struct PrecisionParams {
+            rows: i64,
+            compute_kind: Kind,
+            allowed_kinds: Vec<Kind>,
+        }
+        tensor_types! {
+            PrecisionParams;
+            ComputeTensor => [rows]: kind = compute_kind;
+            PluginTensor => [rows]: kinds = allowed_kinds;
+        }
+        let params = PrecisionParams {
+            rows: 2,
+            compute_kind: Kind::Half,
+            allowed_kinds: vec![Kind::Float, Kind::Double],
+        };
+        let t = Tensor::zeros([2], (Kind::Half, Device::Cpu));
+        assert!(ComputeTensor::new(t, &params).is_ok());
+        let t = Tensor::zeros([2], (Kind::Double, Device::Cpu));
+        assert!(PluginTensor::new(t, &params).is_ok());
+        let t = Tensor::zeros([2], (Kind::Half, Device::Cpu));
+        assert!(matches!(
+            PluginTensor::new(t, &params),
+            Err(TensorTypeError::KindMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_dimension_expressions() {
+        let params = setup();
+        tensor_type!(
+            FlatTensor,
+            [
+                my_param1,
+                my_param2 * my_param3,
+                my_param2 + my_param1 * my_param3
+            ],
+            Params,
+            Kind::Float
+        );
+        assert_eq!(FlatTensor::expected_size(&params).unwrap(), vec![1, 6, 5]);
+        assert_eq!(FlatTensor::expected_numel(&params).unwrap(), 30);
+
+        let t = Tensor::randn([1, 6, 5], (Kind::Float, Device::Cpu));
+        assert!(FlatTensor::new(t, &params).is_ok());
+
+        // The expression is reported as the name of a mismatched axis.
+        let t = Tensor::randn([1, 5, 5], (Kind::Float, Device::Cpu));
+        match FlatTensor::new(t, &params) {
+            Err(TensorTypeError::ShapeMismatch { dim_names, .. }) => assert_eq!(
+                dim_names,
+                vec![
+                    "my_param1",
+                    "my_param2 * my_param3",
+                    "my_param2 + my_param1 * my_param3"
+                ]
+            ),
+            _ => panic!("expected ShapeMismatch"),
+        };
+    }
+
+    #[test]
+    fn test_dimension_overflow() {
+        pub struct HugeParams {
+            rows: i64,
+            cols: i64,
+        }
+        let params = HugeParams {
+            rows: 1 << 40,
+            cols: 1 << 30,
+        };
+
+        // A product of fields that overflows is reported rather than wrapped.
+        tensor_type!(FlatTensor, [rows * cols], HugeParams, Kind::Float);
+        match FlatTensor::expected_size(&params) {
+            Err(TensorTypeError::DimensionOverflow { type_name, value }) => {
+                assert_eq!(type_name, "FlatTensor");
+                assert_eq!(value, "1099511627776 * 1073741824");
+            }
+            _ => panic!("expected DimensionOverflow"),
+        };
+        assert_eq!(
+            FlatTensor::expected_size(&params).unwrap_err().to_string(),
+            "dimension overflow on TensorType \"FlatTensor\": 1099511627776 * 1073741824 is out of the range of i64"
+        );
+
+        // So is a number of elements that overflows.
+        tensor_type!(Matrix, [rows, cols], HugeParams, Kind::Float);
+        assert_eq!(
+            Matrix::expected_size(&params).unwrap(),
+            vec![1 << 40, 1 << 30]
+        );
+        assert!(matches!(
+            Matrix::expected_numel(&params),
+            Err(TensorTypeError::DimensionOverflow { .. })
+        ));
+
+        // So is a field whose value is out of the range of i64, rather than panicking.
+        parameter_type!(WideParam, u64);
+        pub struct WideParams {
+            rows: WideParam,
+            cols: u64,
+        }
+        tensor_type!(WideTensor, [rows, cols], WideParams, Kind::Float);
+        let params = WideParams {
+            rows: WideParam(u64::MAX),
+            cols: 2,
+        };
+        match WideTensor::expected_size(&params) {
+            Err(TensorTypeError::DimensionOverflow { type_name, value }) => {
+                assert_eq!(type_name, "WideTensor");
+                assert_eq!(value, "WideParam(18446744073709551615)");
+            }
+            _ => panic!("expected DimensionOverflow"),
+        };
+        let params = WideParams {
+            rows: WideParam(2),
+            cols: u64::MAX,
+        };
+        assert!(matches!(
+            WideTensor::expected_size(&params),
+            Err(TensorTypeError::DimensionOverflow { .. })
+        ));
+        let params = WideParams {
+            rows: WideParam(2),
+            cols: 3,
+        };
+        assert_eq!(WideTensor::expected_size(&params).unwrap(), vec![2, 3]);
+    }
+
+    #[test]
+    fn test_shape_diff() {
+        let params = setup();
+        let t = Tensor::zeros([1, 2, 3], (Kind::Float, Device::Cpu));
+        assert!(MyTensor::shape_diff(&t, &params).unwrap().is_empty());
+
+        let t = Tensor::zeros([1, 4, 5], (Kind::Float, Device::Cpu));
+        assert_eq!(
+            MyTensor::shape_diff(&t, &params).unwrap(),
+            vec![(1, 2, 4), (2, 3, 5)]
+        );
+
+        // Absent axes are reported with size 0.
+        let t = Tensor::zeros([1, 2], (Kind::Float, Device::Cpu));
+        assert_eq!(MyTensor::shape_diff(&t, &params).unwrap(), vec![(2, 3, 0)]);
+    }
+
+    #[test]
+    fn test_rounded_dimensions() {
+        pub struct FloatParams {
+            length: f64,
+            ratio: f32,
+        }
+        tensor_type!(
+            ScaledTensor,
+            rounded [length, length * ratio],
+            FloatParams,
+            Kind::Float
+        );
+
+        let params = FloatParams {
+            length: 4.0,
+            ratio: 0.5,
+        };
+        assert_eq!(ScaledTensor::expected_size(&params).unwrap(), vec![4, 2]);
+        let t = Tensor::randn([4, 2], (Kind::Float, Device::Cpu));
+        assert!(ScaledTensor::new(t, &params).is_ok());
+
+        let params = FloatParams {
+            length: 5.0,
+            ratio: 0.5,
+        };
+        match ScaledTensor::expected_size(&params) {
+            Err(TensorTypeError::NonIntegralDimension {
+                type_name,
+                axis,
+                value,
+            }) => {
+                if type_name != "ScaledTensor" || axis != 1 || value != 2.5 {
+                    panic!("expected NonIntegralDimension, but unexpected type_name ({}), axis ({}) or value ({})", type_name, axis, value)
+                }
+            }
+            _ => panic!("expected NonIntegralDimension"),
+        };
+    }
+
+    #[test]
+    fn test_rounded_dimensions_overflow() {
+        pub struct FloatParams {
+            length: f64,
+        }
+        tensor_type!(ScaledTensor, rounded[length], FloatParams, Kind::Float);
+
+        // NaN, infinite, and out of range values aren't saturated into a dimension.
+        for length in [f64::NAN, f64::INFINITY, f64::NEG_INFINITY, 1e19, -1e19] {
+            let params = FloatParams { length };
+            match ScaledTensor::expected_size(&params) {
+                Err(TensorTypeError::DimensionOverflow { type_name, value }) => {
+                    assert_eq!(type_name, "ScaledTensor");
+                    assert_eq!(value, length.to_string());
+                }
+                _ => panic!("expected DimensionOverflow for {length}"),
+            };
+        }
+    }
+
+    #[test]
+    fn test_dimension_bounds() {
+        let params = setup();
+        tensor_type!(
+            BoundedTensor,
+            [my_param1 in 1..=4, my_param2 * my_param3 in 1..=5],
+            Params,
+            Kind::Float
+        );
+        match BoundedTensor::expected_size(&params) {
+            Err(TensorTypeError::DimensionOutOfRange {
+                type_name,
+                axis,
+                value,
+                min,
+                max,
+            }) => {
+                if type_name != "BoundedTensor" || axis != 1 || value != 6 || min != 1 || max != 5 {
+                    panic!("expected DimensionOutOfRange, but unexpected type_name ({}), axis ({}), value ({}), min ({}) or max ({})", type_name, axis, value, min, max)
+                }
+            }
+            _ => panic!("expected DimensionOutOfRange"),
+        };
+        let t = Tensor::randn([1, 6], (Kind::Float, Device::Cpu));
+        assert!(matches!(
+            BoundedTensor::new(t, &params),
+            Err(TensorTypeError::DimensionOutOfRange { .. })
+        ));
+
+        // Dimensions without bounds aren't limited.
+        tensor_type!(
+            PartlyBoundedTensor,
+            [my_param1 in 1..=4, my_param2 * my_param3],
+            Params,
+            Kind::Float
+        );
+        let t = Tensor::randn([1, 6], (Kind::Float, Device::Cpu));
+        assert!(PartlyBoundedTensor::new(t, &params).is_ok());
+    }
+
+    #[test]
+    fn test_optional_batch() {
+        let params = setup();
+        tensor_type!(
+            MaybeBatched,
+            [my_param1?, my_param2, my_param3],
+            Params,
+            Kind::Float
+        );
+        assert_eq!(MaybeBatched::expected_size(&params).unwrap(), vec![1, 2, 3]);
+
+        // Both batched and unbatched tensors are accepted.
+        let batched = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        assert!(MaybeBatched::new(batched, &params).is_ok());
+        let single = Tensor::randn([2, 3], (Kind::Float, Device::Cpu));
+        let single = MaybeBatched::new(single, &params).unwrap();
+
+        let batched = single.ensure_batched(&params).unwrap();
+        assert_eq!(batched.size(), &[1, 2, 3]);
+        assert_eq!(batched.ensure_batched(&params).unwrap().size(), &[1, 2, 3]);
+
+        // Other shapes are reported against the full shape.
+        let t = Tensor::randn([3], (Kind::Float, Device::Cpu));
+        match MaybeBatched::new(t, &params) {
+            Err(TensorTypeError::ShapeMismatch {
+                dim_names,
+                expected,
+                ..
+            }) => {
+                if dim_names != vec!["my_param1", "my_param2", "my_param3"]
+                    || expected != vec![1, 2, 3]
+                {
+                    panic!("expected ShapeMismatch, but unexpected dim_names ({:?}) or expected ({:?})", dim_names, expected)
+                }
+            }
+            _ => panic!("expected ShapeMismatch"),
+        };
+    }
+
+    #[test]
+    fn test_const_tensor() {
+        type Fixed = ConstTensor<1, 2, 3>;
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let fixed = Fixed::new(t).unwrap();
+        assert_eq!(fixed.size(), &[1, 2, 3]);
+        assert_eq!(Fixed::expected_size(&()).unwrap(), vec![1, 2, 3]);
+
+        match Fixed::new(Tensor::randn([1, 3, 2], (Kind::Float, Device::Cpu))) {
+            Err(TensorTypeError::ShapeMismatch {
+                type_name,
+                dim_names,
+                expected,
+                found,
+            }) => {
+                if type_name != "ConstTensor"
+                    || dim_names != vec!["B", "S", "D"]
+                    || expected != vec![1, 2, 3]
+                    || found != vec![1, 3, 2]
+                {
+                    panic!("expected ShapeMismatch, but unexpected type_name ({}), dim_names ({:?}), expected ({:?}) or found ({:?})", type_name, dim_names, expected, found)
+                }
+            }
+            _ => panic!("expected ShapeMismatch"),
+        };
+
+        // The kind is Float, and zero dimensions are rejected.
+        assert!(matches!(
+            Fixed::new(Tensor::zeros([1, 2, 3], (Kind::Double, Device::Cpu))),
+            Err(TensorTypeError::KindMismatch { .. })
+        ));
+        match ConstTensor::<1, 0, 3>::expected_size(&()) {
+            Err(TensorTypeError::InvalidDimension { axis, value, .. }) => {
+                assert_eq!((axis, value), (1, 0))
+            }
+            _ => panic!("expected InvalidDimension"),
+        }
+
+        // It converts to and from the tensor types declared with the macro.
+        let params = setup();
+        let my_tensor: MyTensor = fixed.reshape_to(&params).unwrap();
+        assert_eq!(my_tensor.size(), &[1, 2, 3]);
+        let fixed: Fixed = my_tensor.reshape_to(&()).unwrap();
+        assert_eq!(fixed.size(), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_scalar() {
+        let params = setup();
+        tensor_type!(Scalar, [], Params, Kind::Float);
+        assert!(Scalar::expected_size(&params).unwrap().is_empty());
+        assert_eq!(Scalar::expected_numel(&params).unwrap(), 1);
+
+        let scalar = Scalar::new(Tensor::from(2.5f32), &params).unwrap();
+        assert_eq!(scalar.double_value(&[]), 2.5);
+
+        // A tensor holding a single element isn't a scalar.
+        let t = Tensor::from_slice(&[2.5f32]);
+        match Scalar::new(t, &params) {
+            Err(TensorTypeError::RankMismatch {
+                type_name,
+                expected_rank,
+                found_rank,
+            }) => {
+                if type_name != "Scalar" || expected_rank != 0 || found_rank != 1 {
+                    panic!("expected RankMismatch, but unexpected type_name ({}), expected_rank ({}) or found_rank ({})", type_name, expected_rank, found_rank)
+                }
+            }
+            _ => panic!("expected RankMismatch"),
+        };
+
+        let zero = Scalar::zeros(&params, Device::Cpu).unwrap();
+        assert_eq!(zero.dim(), 0);
+    }
+
+    #[test]
+    fn test_dynamic() {
+        tensor_type!(DynTensor, dynamic, Kind::Float);
+        let t = Tensor::zeros([2, 3], (Kind::Float, Device::Cpu));
+        let dyn_tensor = DynTensor::new(t, &[2, 3]).unwrap();
+        assert_eq!(dyn_tensor.size(), vec![2, 3]);
+        assert_eq!(DynTensor::expected_size(&[4, 5]).unwrap(), vec![4, 5]);
+        assert_eq!(DynTensor::ones(&[4], Device::Cpu).unwrap().size(), vec![4]);
+
+        match dyn_tensor.verify(&[2, 4]) {
+            Err(TensorTypeError::ShapeMismatch {
+                type_name,
+                dim_names,
+                expected,
+                found,
+            }) => {
+                if type_name != "DynTensor"
+                    || !dim_names.is_empty()
+                    || expected != vec![2, 4]
+                    || found != vec![2, 3]
+                {
+                    panic!("expected ShapeMismatch, but unexpected type_name ({}), dim_names ({:?}), expected ({:?}) or found ({:?})", type_name, dim_names, expected, found)
+                }
+            }
+            _ => panic!("expected ShapeMismatch"),
+        };
+
+        // The kind is still checked.
+        let t = Tensor::zeros([2, 3], (Kind::Int64, Device::Cpu));
+        match DynTensor::new(t, &[2, 3]) {
+            Err(TensorTypeError::KindMismatch { .. }) => (),
+            _ => panic!("expected KindMismatch"),
+        };
+    }
+
+    #[test]
+    fn test_new_from_map() {
+        use std::collections::HashMap;
+
+        let mut map = HashMap::from([
+            ("my_param1".to_string(), 1),
+            ("my_param2".to_string(), 2),
+            ("my_param3".to_string(), 3),
+        ]);
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        assert!(MyTensor::new_from_map(t, &map).is_ok());
+
+        let t = Tensor::randn([1, 2, 4], (Kind::Float, Device::Cpu));
+        assert!(matches!(
+            MyTensor::new_from_map(t, &map),
+            Err(TensorTypeError::ShapeMismatch { .. })
+        ));
+
+        map.remove("my_param2");
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        match MyTensor::new_from_map(t, &map) {
+            Err(TensorTypeError::MissingDimension { type_name, name }) => {
+                if type_name != "MyTensor" || name != "my_param2" {
+                    panic!(
+                        "expected MissingDimension, but unexpected type_name ({}) or name ({})",
+                        type_name, name
+                    )
+                }
+            }
+            _ => panic!("expected MissingDimension"),
+        };
+
+        // The generic parameter of the checks doesn't clash with a type named F.
+        tensor_type!(F, [my_param2, my_param3], Params, Kind::Float);
+        let map = HashMap::from([("my_param2".to_string(), 2), ("my_param3".to_string(), 3)]);
+        let t = Tensor::randn([2, 3], (Kind::Float, Device::Cpu));
+        assert!(F::new_from_map(t, &map).is_ok());
+    }
+
+    #[test]
+    fn test_invalid_dimension() {
+        // A zero or negative dimension is a configuration error, reported before the tensor's
+        // shape is checked.
+        let params = Params {
+            my_param1: MyParam1(1),
+            my_param2: MyParam2(0),
+            my_param3: MyParam3(3),
+        };
+        let t = Tensor::randn([1, 0, 3], (Kind::Float, Device::Cpu));
+        match MyTensor::new(t, &params) {
+            Err(TensorTypeError::InvalidDimension {
+                type_name,
+                axis,
+                value,
+            }) => {
+                if type_name != "MyTensor" || axis != 1 || value != 0 {
+                    panic!("expected InvalidDimension, but unexpected type_name ({}), axis ({}) or value ({})", type_name, axis, value)
+                }
+            }
+            _ => panic!("expected InvalidDimension"),
+        };
+    }
+
+    #[test]
+    fn test_wrong_kind() {
+        let params = setup();
+
+        // It's an error to call new() with the wrong kind of tensor.
+        let t = Tensor::from_slice(&[1, 2, 3, 4, 5, 6])
+            .reshape([1, 2, 3])
+            .to_kind(Kind::Int64);
+        match MyTensor::new(t, &params) {
+            Err(TensorTypeError::KindMismatch {
+                type_name,
+                expected,
+                found,
+            }) => {
+                if type_name != "MyTensor" || expected != vec![Kind::Float] || found != Kind::Int64
+                {
+                    panic!("expected ShapeMismatch, but unexpected type_name ({}), found ({:?}) or expected ({:?})", type_name, found, expected)
+                }
+            }
+            _ => panic!("expected KindMismatch"),
+        };
+    }
+
+    #[test]
+    fn test_multiple_errors() {
+        let params = setup();
+
+        // A tensor of the wrong shape and the wrong kind gets both errors.
+        let t = Tensor::zeros([2, 2, 3], (Kind::Int64, Device::Cpu));
+        match MyTensor::new(t, &params) {
+            Err(TensorTypeError::Multiple(errors)) => {
+                assert_eq!(errors.len(), 2);
+                assert!(matches!(errors[0], TensorTypeError::ShapeMismatch { .. }));
+                assert!(matches!(errors[1], TensorTypeError::KindMismatch { .. }));
+            }
+            _ => panic!("expected Multiple"),
+        };
+    }
+
+    #[test]
+    fn test_detailed_error() {
+        let params = setup();
+        let t = Tensor::zeros([1, 5, 3], (Kind::Float, Device::Cpu));
+        let err = MyTensor::new(t, &params).unwrap_err();
+        assert_eq!(
+            err.detailed(),
+            [
+                "shape mismatch on TensorType \"MyTensor\":",
+                "  axis      0         1         2",
+                "  name      my_param1 my_param2 my_param3",
+                "  expected  1         2         3",
+                "  found     1         5         3",
+                "                      ^",
+            ]
+            .join("\n")
+        );
+    }
+
+    #[test]
+    fn test_kind_from_params() {
+        pub struct PrecisionParams {
+            rows: i64,
+            compute_kind: Kind,
+        }
+        tensor_type!(Matrix, [rows], PrecisionParams, kind = compute_kind);
+        assert!(Matrix::KINDS.is_empty());
+
+        for compute_kind in [Kind::Float, Kind::Half] {
+            let params = PrecisionParams {
+                rows: 2,
+                compute_kind,
+            };
+            let t = Tensor::ones([2], (compute_kind, Device::Cpu));
+            assert!(Matrix::new(t, &params).is_ok());
+            let zeros = Matrix::zeros(&params, Device::Cpu).unwrap();
+            assert_eq!(zeros.kind(), compute_kind);
+        }
+
+        let params = PrecisionParams {
+            rows: 2,
+            compute_kind: Kind::Half,
+        };
+        let t = Tensor::ones([2], (Kind::Float, Device::Cpu));
+        match Matrix::new(t, &params) {
+            Err(TensorTypeError::KindMismatch {
+                expected, found, ..
+            }) => {
+                if expected != vec![Kind::Half] || found != Kind::Float {
+                    panic!(
+                        "expected KindMismatch, but unexpected found ({:?}) or expected ({:?})",
+                        found, expected
+                    )
+                }
+            }
+            _ => panic!("expected KindMismatch"),
+        };
+    }
+
+    #[test]
+    fn test_kinds_from_params() {
+        pub struct PluginParams {
+            rows: i64,
+            allowed_kinds: Vec<Kind>,
+        }
+        tensor_type!(Matrix, [rows], PluginParams, kinds = allowed_kinds);
+        assert!(Matrix::KINDS.is_empty());
+
+        let params = PluginParams {
+            rows: 2,
+            allowed_kinds: vec![Kind::Half, Kind::Float],
+        };
+        for kind in [Kind::Float, Kind::Half] {
+            let t = Tensor::ones([2], (kind, Device::Cpu));
+            assert!(Matrix::new(t, &params).is_ok());
+        }
+        // Tensors are created with the first kind in the set.
+        let zeros = Matrix::zeros(&params, Device::Cpu).unwrap();
+        assert_eq!(zeros.kind(), Kind::Half);
+
+        let t = Tensor::ones([2], (Kind::Double, Device::Cpu));
+        match Matrix::new(t, &params) {
+            Err(TensorTypeError::KindMismatch {
+                expected, found, ..
+            }) => {
+                if expected != vec![Kind::Half, Kind::Float] || found != Kind::Double {
+                    panic!(
+                        "expected KindMismatch, but unexpected found ({:?}) or expected ({:?})",
+                        found, expected
+                    )
+                }
+            }
+            _ => panic!("expected KindMismatch"),
+        };
+
+        // An empty set of kinds accepts no tensor, and can't give a kind to create tensors with.
+        let params = PluginParams {
+            rows: 2,
+            allowed_kinds: vec![],
+        };
+        let t = Tensor::ones([2], (Kind::Float, Device::Cpu));
+        assert!(matches!(
+            Matrix::new(t, &params),
+            Err(TensorTypeError::EmptyKindSet { .. })
+        ));
+        assert!(matches!(
+            Matrix::creation_kind(&params),
+            Err(TensorTypeError::EmptyKindSet { .. })
+        ));
+        assert!(matches!(
+            Matrix::zeros(&params, Device::Cpu),
+            Err(TensorTypeError::EmptyKindSet { .. })
+        ));
+    }
+
+    #[test]
+    fn test_rank_only() {
+        let params = setup();
+        tensor_type!(RankTensor, rank = 3, Params, Kind::Float);
+
+        // Any sizes are accepted so long as the tensor has three dimensions.
+        let t = Tensor::randn([4, 5, 6], (Kind::Float, Device::Cpu));
+        assert!(RankTensor::new(t, &params).is_ok());
+
+        let t = Tensor::randn([4, 5], (Kind::Float, Device::Cpu));
+        match RankTensor::new(t, &params) {
+            Err(TensorTypeError::RankMismatch {
+                type_name,
+                expected_rank,
+                found_rank,
+            }) => {
+                if type_name != "RankTensor" || expected_rank != 3 || found_rank != 2 {
+                    panic!("expected RankMismatch, but unexpected type_name ({}), found ({:?}) or expected ({:?})", type_name, found_rank, expected_rank)
+                }
+            }
+            _ => panic!("expected RankMismatch"),
+        };
+
+        // The kind is still checked.
+        let t = Tensor::randn([4, 5, 6], (Kind::Double, Device::Cpu));
+        assert!(matches!(
+            RankTensor::new(t, &params),
+            Err(TensorTypeError::KindMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_kind_set() {
+        let params = setup();
+        tensor_type!(
+            MixedTensor,
+            [my_param1, my_param2, my_param3],
+            Params,
+            [Kind::Float, Kind::Double]
+        );
+
+        // Tensors are created with the first kind in the set.
+        assert_eq!(MixedTensor::expected_kind(), Kind::Float);
+
+        // Any kind in the set is accepted.
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        assert!(MixedTensor::new(t, &params).is_ok());
+        let t = Tensor::randn([1, 2, 3], (Kind::Double, Device::Cpu));
+        assert!(MixedTensor::new(t, &params).is_ok());
+
+        // Other kinds are rejected, reporting the full set of allowed kinds.
+        let t = Tensor::randn([1, 2, 3], (Kind::Half, Device::Cpu));
+        match MixedTensor::new(t, &params) {
+            Err(TensorTypeError::KindMismatch {
+                type_name,
+                expected,
+                found,
+            }) => {
+                if type_name != "MixedTensor"
+                    || expected != vec![Kind::Float, Kind::Double]
+                    || found != Kind::Half
+                {
+                    panic!("expected KindMismatch, but unexpected type_name ({}), found ({:?}) or expected ({:?})", type_name, found, expected)
+                }
+            }
+            _ => panic!("expected KindMismatch"),
+        };
+    }
+
+    #[test]
+    fn test_kind_class() {
+        let params = setup();
+        tensor_type!(AnyFloatTensor, [my_param2], Params, KindClass::AnyFloat);
+        tensor_type!(AnyIntTensor, [my_param2], Params, KindClass::AnyInt);
+        assert_eq!(AnyFloatTensor::KINDS, KindClass::AnyFloat.kinds());
+        assert_eq!(AnyFloatTensor::expected_kind(), Kind::Float);
+        assert_eq!(AnyIntTensor::expected_kind(), Kind::Int64);
+
+        for kind in [Kind::Float, Kind::Double, Kind::Half] {
+            let t = Tensor::zeros([2], (kind, Device::Cpu));
+            assert!(AnyFloatTensor::new(t, &params).is_ok());
+        }
+        let t = Tensor::zeros([2], (Kind::Int, Device::Cpu));
+        assert!(AnyIntTensor::new(t, &params).is_ok());
+        assert_eq!(
+            AnyIntTensor::zeros(&params, Device::Cpu).unwrap().kind(),
+            Kind::Int64
+        );
+
+        // The error names the category.
+        let t = Tensor::zeros([2], (Kind::Int, Device::Cpu));
+        let err = AnyFloatTensor::new(t, &params).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("expected any floating point kind [Float, Double, Half, BFloat16]"));
+    }
+
+    #[test]
+    fn test_new_unchecked() {
+        // new_unchecked() wraps the tensor without checking it.
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let my_tensor = MyTensor::new_unchecked(t);
+        assert_eq!(my_tensor.size(), &[1, 2, 3]);
+
+        // So it's up to the caller to ensure the tensor has the right shape and kind.
+        let t = Tensor::randn([3, 2], (Kind::Double, Device::Cpu));
+        let my_tensor = MyTensor::new_unchecked(t);
+        assert_eq!(my_tensor.size(), &[3, 2]);
+    }
+
+    #[test]
+    fn test_assert_shape() {
+        let params = setup();
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        assert!(assert_shape!(MyTensor, &t, &params).is_ok());
+
+        // Only the shape is checked, not the kind.
+        let t = Tensor::randn([1, 2, 3], (Kind::Double, Device::Cpu));
+        assert!(assert_shape!(MyTensor, &t, &params).is_ok());
+
+        // The error is the one new() returns.
+        let t = Tensor::randn([1, 2, 1], (Kind::Float, Device::Cpu));
+        let err = assert_shape!(MyTensor, &t, &params).unwrap_err();
+        assert_eq!(
+            err.to_string(),
+            MyTensor::new(t, &params).unwrap_err().to_string()
+        );
+    }
+
+    #[test]
+    fn test_verify() {
+        let params = setup();
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let mut my_tensor = MyTensor::new(t, &params).unwrap();
+        assert!(my_tensor.verify(&params).is_ok());
+
+        // Modifying the public tensor in place can break the shape invariant, which verify()
+        // reports.
+        let _ = my_tensor.tensor.resize_([1, 2, 1]);
+        assert!(matches!(
+            my_tensor.verify(&params),
+            Err(TensorTypeError::ShapeMismatch { .. })
+        ));
+        assert!(matches!(
+            my_tensor.into_inner_checked(&params),
+            Err(TensorTypeError::ShapeMismatch { .. })
+        ));
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let my_tensor = MyTensor::new(t, &params).unwrap();
+        assert_eq!(
+            my_tensor.into_inner_checked(&params).unwrap().size(),
+            &[1, 2, 3]
+        );
+
+        // The kind is checked as well.
+        let t = Tensor::randn([1, 2, 3], (Kind::Double, Device::Cpu));
+        assert!(matches!(
+            MyTensor::new_unchecked(t).verify(&params),
+            Err(TensorTypeError::KindMismatch { .. })
+        ));
+
+        // check() makes the same checks on a tensor without wrapping it.
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        assert!(MyTensor::check(&t, &params).is_ok());
+        assert!(matches!(
+            MyTensor::check(&t.to_kind(Kind::Double), &params),
+            Err(TensorTypeError::KindMismatch { .. })
+        ));
+        assert!(matches!(
+            MyTensor::check(&t.reshape([3, 2]), &params),
+            Err(TensorTypeError::ShapeMismatch { .. })
+        ));
+    }
+
+    mod public {
+        use super::Params;
+        use tch::Kind;
+        use tensor_types::tensor_type;
+
+        tensor_type!(
+            #[public_tensor]
+            PublicTensor,
+            [my_param1, my_param2, my_param3],
+            Params,
+            Kind::Float
+        );
+    }
+
+    mod encoder {
+        use super::Params;
+        use tch::Kind;
+        use tensor_types::tensor_type;
+
+        tensor_type!(Embedding, [my_param2, my_param3], Params, Kind::Float);
+    }
+
+    mod decoder {
+        use super::Params;
+        use tch::Kind;
+        use tensor_types::tensor_type;
+
+        tensor_type!(Embedding, [my_param3], Params, Kind::Float);
+    }
+
+    #[test]
+    fn test_same_name_in_modules() {
+        use decoder::Embedding as DecoderEmbedding;
+        use encoder::Embedding as EncoderEmbedding;
+
+        let params = setup();
+        let encoder = EncoderEmbedding::zeros(&params, Device::Cpu).unwrap();
+        let decoder = DecoderEmbedding::zeros(&params, Device::Cpu).unwrap();
+        assert_eq!(encoder.size(), &[2, 3]);
+        assert_eq!(decoder.size(), &[3]);
+        assert_eq!(EncoderEmbedding::NAME, "Embedding");
+    }
+
+    #[test]
+    fn test_public_tensor() {
+        let params = setup();
+        let t = Tensor::zeros([1, 2, 3], (Kind::Float, Device::Cpu));
+        let mut public_tensor = public::PublicTensor::new(t, &params).unwrap();
+        // The field of a #[public_tensor] type can be replaced from outside its module, so the
+        // tensor must be verified again.
+        public_tensor.tensor = Tensor::zeros([3], (Kind::Float, Device::Cpu));
+        assert!(public_tensor.verify(&params).is_err());
+    }
+
+    mod config {
+        pub struct Params {
+            batch_size: i64,
+            seq_len: i64,
+        }
+
+        impl Params {
+            pub fn new(batch_size: i64, seq_len: i64) -> Self {
+                Self {
+                    batch_size,
+                    seq_len,
+                }
+            }
+
+            pub fn batch_size(&self) -> i64 {
+                self.batch_size
+            }
+
+            pub fn seq_len(&self) -> i64 {
+                self.seq_len
+            }
+        }
+    }
+
+    #[test]
+    fn test_getters() {
+        tensor_type!(
+            BatchSeq,
+            getters [batch_size, seq_len * batch_size],
+            self::config::Params,
+            Kind::Float
+        );
+        let params = config::Params::new(2, 3);
+        let t = Tensor::zeros([2, 6], (Kind::Float, Device::Cpu));
+        assert!(BatchSeq::new(t, &params).is_ok());
+        assert_eq!(BatchSeq::expected_size(&params).unwrap(), vec![2, 6]);
+        match BatchSeq::new(Tensor::zeros([2, 3], (Kind::Float, Device::Cpu)), &params) {
+            Err(TensorTypeError::ShapeMismatch { dim_names, .. }) => {
+                assert_eq!(dim_names, vec!["batch_size", "seq_len * batch_size"]);
+            }
+            _ => panic!("expected ShapeMismatch"),
+        };
+    }
+
+    pub trait HasDims {
+        fn batch_size(&self) -> i64;
+        fn seq_len(&self) -> i64;
+    }
+
+    impl HasDims for config::Params {
+        fn batch_size(&self) -> i64 {
+            self.batch_size()
+        }
+
+        fn seq_len(&self) -> i64 {
+            self.seq_len()
+        }
+    }
+
+    #[test]
+    fn test_params_trait() {
+        struct TrainingConfig {
+            batch_size: i64,
+        }
+        impl HasDims for TrainingConfig {
+            fn batch_size(&self) -> i64 {
+                self.batch_size
+            }
+
+            fn seq_len(&self) -> i64 {
+                3
+            }
+        }
+        tensor_type!(BatchSeq, getters [batch_size, seq_len], dyn HasDims, Kind::Float);
+
+        // Any type implementing the trait provides the dimensions.
+        let t = Tensor::zeros([2, 3], (Kind::Float, Device::Cpu));
+        let batch_seq = BatchSeq::new(t, &config::Params::new(2, 3)).unwrap();
+        assert!(batch_seq.verify(&TrainingConfig { batch_size: 2 }).is_ok());
+        assert!(batch_seq.verify(&TrainingConfig { batch_size: 4 }).is_err());
+    }
+
+    #[test]
+    fn test_lazy_dimensions() {
+        pub struct LazyParams {
+            batch_size: Box<dyn Fn() -> i64>,
+            calls: std::cell::Cell<usize>,
+        }
+        impl LazyParams {
+            fn batch_size(&self) -> i64 {
+                self.calls.set(self.calls.get() + 1);
+                (self.batch_size)()
+            }
+
+            fn d_model(&self) -> i64 {
+                4
+            }
+        }
+        tensor_type!(Lazy, [batch_size(), d_model()], LazyParams, Kind::Float);
+
+        let params = LazyParams {
+            batch_size: Box::new(|| 2),
+            calls: std::cell::Cell::new(0),
+        };
+        assert_eq!(params.calls.get(), 0);
+        let t = Tensor::zeros([2, 4], (Kind::Float, Device::Cpu));
+        let lazy = Lazy::new(t, &params).unwrap();
+        assert_eq!(params.calls.get(), 1);
+        assert!(lazy.verify(&params).is_ok());
+        assert_eq!(params.calls.get(), 2);
+    }
+
+    #[test]
+    fn test_new_ref() {
+        let params = setup();
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let view = MyTensor::new_ref(&t, &params).unwrap();
+        assert_eq!(view.size(), &[1, 2, 3]);
+        assert_eq!(view.tensor().data_ptr(), t.data_ptr());
+        assert!(format!("{:?}", view).starts_with("TensorRef<MyTensor>"));
+
+        let t = Tensor::randn([1, 2, 3], (Kind::Double, Device::Cpu));
+        assert!(matches!(
+            MyTensor::new_ref(&t, &params),
+            Err(TensorTypeError::KindMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_tensor_mut() {
+        let params = setup();
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let mut my_tensor = MyTensor::new(t, &params).unwrap();
+
+        // In-place operations that keep the shape and kind are fine.
+        let _ = my_tensor.tensor_mut(&params).fill_(1.0);
+        assert_eq!(my_tensor.sum(Kind::Float).double_value(&[]), 6.0);
+
+        // With the Warn action, an invalid mutation is logged, with the trace feature, but doesn't
+        // panic.
+        tensor_type!(
+            WarnTensor,
+            [my_param1, my_param2, my_param3],
+            Params,
+            Kind::Float,
+            on_invalid_mut = InvalidMutAction::Warn
+        );
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let mut warn_tensor = WarnTensor::new(t, &params).unwrap();
+        let _ = warn_tensor.tensor_mut(&params).resize_([1, 2, 1]);
+        assert!(warn_tensor.verify(&params).is_err());
+    }
+
+    #[test]
+    #[should_panic(expected = "invalid mutation through tensor_mut()")]
+    fn test_tensor_mut_invalid() {
+        let params = setup();
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let mut my_tensor = MyTensor::new(t, &params).unwrap();
+        let _ = my_tensor.tensor_mut(&params).resize_([1, 2, 1]);
+    }
+
+    #[test]
+    fn test_try_from() {
+        pub struct DefaultParams {
+            rows: i64,
+            cols: i64,
+        }
+        impl Default for DefaultParams {
+            fn default() -> Self {
+                DefaultParams { rows: 2, cols: 3 }
+            }
+        }
+        tensor_type!(DefaultTensor, [rows, cols], DefaultParams, Kind::Float);
+
+        // The tensor is checked against the default parameters.
+        let t = Tensor::randn([2, 3], (Kind::Float, Device::Cpu));
+        let default_tensor = DefaultTensor::try_from(t).unwrap();
+        assert_eq!(default_tensor.size(), &[2, 3]);
+
+        let t = Tensor::randn([3, 2], (Kind::Float, Device::Cpu));
+        let result: Result<DefaultTensor, TensorTypeError> = t.try_into();
+        assert!(matches!(result, Err(TensorTypeError::ShapeMismatch { .. })));
+
+        // The generic parameter of the impls doesn't clash with a type or parameters type named P.
+        mod named_p {
+            use tch::Kind;
+            use tensor_types::tensor_type;
+
+            #[derive(Default)]
+            pub struct P {
+                rows: i64,
+            }
+            tensor_type!(PTensor, [rows], P, Kind::Float);
+
+            pub mod tensor {
+                use tch::Kind;
+                use tensor_types::tensor_type;
+                tensor_type!(P, [rows], super::P, Kind::Float);
+            }
+        }
+        let t = Tensor::zeros([0], (Kind::Float, Device::Cpu));
+        let result = named_p::PTensor::try_from(t);
+        assert!(matches!(
+            result,
+            Err(TensorTypeError::InvalidDimension { .. })
+        ));
+        let t = Tensor::zeros([0], (Kind::Float, Device::Cpu));
+        let result = named_p::tensor::P::try_from(t);
+        assert!(matches!(
+            result,
+            Err(TensorTypeError::InvalidDimension { .. })
+        ));
+    }
+
+    #[test]
+    fn test_default() {
+        pub struct DefaultParams {
+            rows: MyParam2,
+            cols: MyParam3,
+        }
+        impl Default for DefaultParams {
+            fn default() -> Self {
+                DefaultParams {
+                    rows: MyParam2(2),
+                    cols: MyParam3(3),
+                }
+            }
+        }
+        tensor_type!(DefaultTensor, [rows, cols], DefaultParams, Kind::Int64);
+        let default_tensor = DefaultTensor::default();
+        assert_eq!(default_tensor.size(), &[2, 3]);
+        assert_eq!(default_tensor.kind(), Kind::Int64);
+    }
+
+    #[test]
+    fn test_types() {
+        let params = setup();
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let my_tensor = MyTensor::new(t, &params).unwrap();
+
+        fn type_of<T>(_: T) -> &'static str {
+            std::any::type_name::<T>()
+        }
+
+        // The type of my_tensor is MyTensor.
+        assert_eq!(type_of(&my_tensor), "&tensor_types_tests::tests::MyTensor");
+        // The type of what it wraps is Tensor.
+        assert_eq!(type_of(&*my_tensor), "&tch::wrappers::tensor::Tensor");
+    }
+
+    #[test]
+    fn test_accessors() {
+        let params = setup();
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let my_tensor = MyTensor::new(t, &params).unwrap();
+
+        // Access the tensor using the tensor() function.
+        assert_eq!(my_tensor.tensor().size(), &[1, 2, 3]);
+
+        // Access the tensor using explicit deref.
+        assert_eq!((*my_tensor).size(), &[1, 2, 3]);
+
+        // Access the tensor using automatic deref.
+        assert_eq!(my_tensor.size(), &[1, 2, 3]);
+
+        // Access the tensor through AsRef and Borrow, as generic code does.
+        fn numel_of(t: impl AsRef<Tensor>) -> usize {
+            t.as_ref().numel()
+        }
+        assert_eq!(numel_of(&my_tensor), 6);
+        let borrowed: &Tensor = std::borrow::Borrow::borrow(&my_tensor);
+        assert_eq!(borrowed.size(), &[1, 2, 3]);
+
+        // Unwrap the inner tensor.
+        let unwrapped_tensor = my_tensor.into_inner();
+        assert_eq!(unwrapped_tensor.size(), &[1, 2, 3]);
+        fn type_of<T>(_: T) -> &'static str {
+            std::any::type_name::<T>()
+        }
+        assert_eq!(type_of(&unwrapped_tensor), "&tch::wrappers::tensor::Tensor");
+
+        // Convert into the inner tensor, as generic code does.
+        let my_tensor = MyTensor::new(unwrapped_tensor, &params).unwrap();
+        let converted: Tensor = my_tensor.into();
+        assert_eq!(converted.size(), &[1, 2, 3]);
+    }
+
+    #[test]
+    fn test_shape_key() {
+        use std::collections::hash_map::DefaultHasher;
+        use std::collections::HashMap;
+        use std::hash::{Hash, Hasher};
+
+        fn hash_of<T: Hash>(value: &T) -> u64 {
+            let mut hasher = DefaultHasher::new();
+            value.hash(&mut hasher);
+            hasher.finish()
+        }
+
+        let params = setup();
+        let a = MyTensor::randn(&params, Device::Cpu).unwrap();
+        let b = MyTensor::randn(&params, Device::Cpu).unwrap();
+        assert_ne!(a, b);
+
+        // Keys and hashes depend only on the shape and kind, not on the values.
+        assert_eq!(a.shape_key(), b.shape_key());
+        assert_eq!(hash_of(&a.shape_key()), hash_of(&b.shape_key()));
+
+        let mut cache = HashMap::new();
+        cache.insert(a.shape_key(), "cached");
+        assert_eq!(cache.get(&b.shape_key()), Some(&"cached"));
+
+        // Types of the same shape have different keys.
+        tensor_type!(
+            OtherTensor,
+            [my_param1, my_param2, my_param3],
+            Params,
+            Kind::Float
+        );
+        let c = OtherTensor::randn(&params, Device::Cpu).unwrap();
+        assert_ne!(a.shape_key(), c.shape_key());
+        assert_eq!(c.shape_key().type_name, "OtherTensor");
+
+        // Tensor types don't implement Hash, so a type can implement its own.
+        impl Hash for OtherTensor {
+            fn hash<H: Hasher>(&self, state: &mut H) {
+                self.size().hash(state);
+            }
+        }
+        assert_eq!(hash_of(&c), hash_of(&c.size()));
+    }
+
+    #[test]
+    fn test_shape_eq() {
+        use std::collections::HashSet;
+
+        // Types marked with #[shape_eq] are equal and hash alike by shape and kind.
+        tensor_type!(
+            #[shape_eq]
+            #[public_tensor]
+            KeyTensor,
+            [my_param2, my_param3],
+            Params,
+            [Kind::Float, Kind::Double]
+        );
+        let params = setup();
+        let a = KeyTensor::randn(&params, Device::Cpu).unwrap();
+        let b = KeyTensor::randn(&params, Device::Cpu).unwrap();
+        assert_eq!(a, b);
+        let c = KeyTensor::new(a.to_kind(Kind::Double), &params).unwrap();
+        assert_ne!(a, c);
+
+        let mut seen = HashSet::new();
+        assert!(seen.insert(a));
+        assert!(!seen.insert(b));
+        assert!(seen.insert(c));
+        assert_eq!(seen.len(), 2);
+    }
+
+    #[test]
+    fn test_display() {
+        let params = setup();
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let my_tensor = MyTensor::new(t, &params).unwrap();
+        assert_eq!(format!("{}", my_tensor), "MyTensor[1, 2, 3] (Float, Cpu)");
+    }
+
+    #[test]
+    fn test_shape_signature() {
+        let params = setup();
+        assert_eq!(
+            MyTensor::shape_signature(&params),
+            "MyTensor[my_param1=1, my_param2=2, my_param3=3]:Float"
+        );
+
+        tensor_type!(
+            FlatTensor,
+            [my_param1, my_param2 * my_param3],
+            Params,
+            [Kind::Float, Kind::Double]
+        );
+        assert_eq!(
+            FlatTensor::shape_signature(&params),
+            "FlatTensor[my_param1=1, my_param2 * my_param3=6]:Float|Double"
+        );
+
+        tensor_type!(RankTensor, rank = 3, Params, Kind::Float);
+        assert_eq!(RankTensor::shape_signature(&params), "RankTensor[]:Float");
+    }
+
+    #[test]
+    fn test_clone() {
+        let params = setup();
+        tensor_type!(MyTensor2, [my_param2, my_param3], Params, Kind::Int);
+        let tensor = Tensor::from_slice(&[0, 1, 2, 3, 4, 5]).reshape([2, 3]);
+        let my_tensor2 = MyTensor2::new(tensor, &params).unwrap();
+        // clone() makes a clone of MyTensor with a shallow_clone of the wrapped tensor.
+        let cloned_wrapper = my_tensor2.clone(&params).unwrap();
+
+        assert_eq!((*cloned_wrapper).size(), vec![2, 3]);
+
+        // shallow_clone() skips the check, and shares its data with the original.
+        let shallow: MyTensor2 = my_tensor2.shallow_clone();
+        assert_eq!(shallow.size(), vec![2, 3]);
+        let _ = shallow.tensor.shallow_clone().fill_(7);
+        assert_eq!(my_tensor2.int64_value(&[0, 0]), 7);
+    }
+
+    #[test]
+    fn test_to_tensor() {
+        let params = setup();
+        let t = Tensor::zeros([1, 2, 3], (Kind::Float, Device::Cpu));
+        let my_tensor = MyTensor::new(t, &params).unwrap();
+
+        // to_tensor() returns a deep copy, so modifying it leaves the wrapped tensor unchanged.
+        let mut copy = my_tensor.to_tensor();
+        let _ = copy.fill_(1.0);
+        assert_eq!(my_tensor.sum(Kind::Float).double_value(&[]), 0.0);
+
+        // The shallow clone made by clone() shares the data.
+        let cloned = my_tensor.clone(&params).unwrap();
+        let _ = cloned.tensor.shallow_clone().fill_(1.0);
+        assert_eq!(my_tensor.sum(Kind::Float).double_value(&[]), 6.0);
+    }
+
+    #[test]
+    fn test_apply_fn() {
+        let params = setup();
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let my_tensor = MyTensor::new(t, &params).unwrap();
+
+        // Test the apply_fn() method.
+        let new_my_tensor = my_tensor.apply_fn(|t| t.fill(1.0), &params).unwrap();
+        assert_eq!(new_my_tensor.tensor().size(), &[1, 2, 3]);
+        let tensor_of_ones = Tensor::ones([1, 2, 3], (Kind::Float, Device::Cpu));
+        assert_eq!(
+            new_my_tensor
+                .eq_tensor(&tensor_of_ones)
+                .all()
+                .int64_value(&[]),
+            1
+        );
+        assert_eq!(
+            tensor_of_ones
+                .eq_tensor(&new_my_tensor)
+                .all()
+                .int64_value(&[]),
+            1
+        );
+
+        // But it's an error if the function given to apply_fn changes the size of the wrapped tensor.
+        // The tensor shape is fixed and is checked.
+        match my_tensor.apply_fn(|t| t.transpose(1, 2), &params) {
+            Err(TensorTypeError::ShapeMismatch {
+                type_name,
+                expected,
+                found,
+                ..
+            }) => {
+                if type_name != "MyTensor" || expected != vec![1, 2, 3] || found != vec![1, 3, 2] {
+                    panic!("expected ShapeMismatch, but unexpected type_name ({}), found ({:?}) or expected ({:?})", type_name, found, expected)
+                }
+            }
+            _ => panic!("expected ShapeMismatch"),
+        };
+    }
+
+    #[test]
+    fn test_convert() {
+        let params = setup();
+        tensor_type!(
+            AttentionQuery,
+            [my_param1, my_param2, my_param3],
+            Params,
+            Kind::Float
+        );
+        tensor_type!(
+            TransposedTensor,
+            [my_param1, my_param3, my_param2],
+            Params,
+            Kind::Float
+        );
+        let my_tensor = MyTensor::randn(&params, Device::Cpu).unwrap();
+
+        // The converted value shares the tensor's data.
+        let query: AttentionQuery = my_tensor.convert(&params).unwrap();
+        assert_eq!(query.data_ptr(), my_tensor.data_ptr());
+
+        assert!(matches!(
+            my_tensor.convert::<TransposedTensor>(&params),
+            Err(TensorTypeError::ShapeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_to_kind_as() {
+        let params = setup();
+        tensor_type!(
+            DoubleTensor,
+            [my_param1, my_param2, my_param3],
+            Params,
+            Kind::Double
+        );
+        let my_tensor = MyTensor::ones(&params, Device::Cpu).unwrap();
+        let double: DoubleTensor = my_tensor.to_kind_as(Kind::Double, &params).unwrap();
+        assert_eq!(double.kind(), Kind::Double);
+
+        match my_tensor.to_kind_as::<DoubleTensor>(Kind::Half, &params) {
+            Err(TensorTypeError::KindMismatch {
+                expected, found, ..
+            }) => {
+                if expected != vec![Kind::Double] || found != Kind::Half {
+                    panic!(
+                        "expected KindMismatch, but unexpected found ({:?}) or expected ({:?})",
+                        found, expected
+                    )
+                }
+            }
+            _ => panic!("expected KindMismatch"),
+        };
+    }
+
+    #[test]
+    fn test_kind_history() {
+        let params = setup();
+        tensor_type!(
+            MixedTensor,
+            [my_param1, my_param2, my_param3],
+            Params,
+            KindClass::AnyFloat
+        );
+        let my_tensor = MyTensor::ones(&params, Device::Cpu).unwrap();
+        let half: MixedTensor = my_tensor.to_kind_as(Kind::Half, &params).unwrap();
+        let float = half.apply_fn(|t| t.to_kind(Kind::Float), &params).unwrap();
+        let scaled = float.apply_fn(|t| t * 2.0, &params).unwrap();
+
+        // The history is recorded only with the cast-history feature.
+        if cfg!(feature = "cast-history") {
+            assert_eq!(
+                scaled.kind_history(),
+                &[Kind::Float, Kind::Half, Kind::Float]
+            );
+        } else {
+            assert!(scaled.kind_history().is_empty());
+        }
+    }
+
+    #[test]
+    fn test_unchecked_release() {
+        let params = setup();
+        assert_eq!(
+            tensor_types::UNCHECKED_RELEASE,
+            cfg!(feature = "unchecked-release")
+        );
+        let t = Tensor::ones([1, 2, 4], (Kind::Float, Device::Cpu));
+        let result = MyTensor::new(t, &params);
+
+        // new() skips its checks only in release builds with the unchecked-release feature, so
+        // verify() must catch the mismatch there.
+        if cfg!(debug_assertions) || !cfg!(feature = "unchecked-release") {
+            assert!(matches!(result, Err(TensorTypeError::ShapeMismatch { .. })));
+        } else {
+            assert!(matches!(
+                result.unwrap().verify(&params),
+                Err(TensorTypeError::ShapeMismatch { .. })
+            ));
+        }
+    }
+
+    #[cfg(feature = "trace")]
+    #[test]
+    fn test_trace() {
+        use std::sync::Mutex;
+
+        // Collect the debug messages so the test can look for the one logged by new().
+        struct Capture(Mutex<Vec<String>>);
+        impl log::Log for Capture {
+            fn enabled(&self, metadata: &log::Metadata) -> bool {
+                metadata.level() <= log::Level::Debug
+            }
+            fn log(&self, record: &log::Record) {
+                self.0.lock().unwrap().push(record.args().to_string());
+            }
+            fn flush(&self) {}
+        }
+        static CAPTURE: Capture = Capture(Mutex::new(Vec::new()));
+        log::set_logger(&CAPTURE).unwrap();
+        log::set_max_level(log::LevelFilter::Debug);
+
+        let params = setup();
+        let t = Tensor::ones([1, 2, 3], (Kind::Float, Device::Cpu));
+        let _ = MyTensor::new(t, &params).unwrap();
+        assert!(CAPTURE
+            .0
+            .lock()
+            .unwrap()
+            .contains(&"MyTensor::new(): [1, 2, 3] (Float, Cpu)".to_string()));
+    }
+
+    #[test]
+    fn test_apply_into() {
+        let params = setup();
+        tensor_type!(
+            TransposedTensor,
+            [my_param1, my_param3, my_param2],
+            Params,
+            Kind::Float
+        );
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let my_tensor = MyTensor::new(t, &params).unwrap();
+
+        // A shape-changing function is applied by naming the target type.
+        let transposed: TransposedTensor = my_tensor
+            .apply_into(|t| t.transpose(1, 2), &params)
+            .unwrap();
+        assert_eq!(transposed.size(), &[1, 3, 2]);
+
+        // The result is still checked against the target type.
+        assert!(matches!(
+            my_tensor.apply_into::<TransposedTensor, _>(|t| t.shallow_clone(), &params),
+            Err(TensorTypeError::ShapeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_apply2() {
+        let params = setup();
+        let x = MyTensor::ones(&params, Device::Cpu).unwrap();
+        let residual = MyTensor::ones(&params, Device::Cpu).unwrap();
+
+        let sum = x.apply2(&residual, |a, b| a + b, &params).unwrap();
+        assert_eq!(sum.double_value(&[0, 1, 2]), 2.0);
+
+        // A function that changes the shape is caught.
+        assert!(matches!(
+            x.apply2(&residual, |a, b| Tensor::cat(&[a, b], 0), &params),
+            Err(TensorTypeError::ShapeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_arithmetic() {
+        let params = setup();
+        let a =
+            MyTensor::new(Tensor::ones([1, 2, 3], (Kind::Float, Device::Cpu)), &params).unwrap();
+        let b =
+            MyTensor::new(Tensor::ones([1, 2, 3], (Kind::Float, Device::Cpu)), &params).unwrap();
+
+        // Values of the same tensor type combine element-wise into the same type.
+        let sum: MyTensor = &a + &b;
+        assert_eq!(sum.size(), &[1, 2, 3]);
+        assert_eq!(sum.sum(Kind::Float).double_value(&[]), 12.0);
+        let difference: MyTensor = &a - &b;
+        assert_eq!(difference.sum(Kind::Float).double_value(&[]), 0.0);
+        let product: MyTensor = &sum * &sum;
+        assert_eq!(product.sum(Kind::Float).double_value(&[]), 24.0);
+        let quotient: MyTensor = product / sum;
+        assert_eq!(quotient.sum(Kind::Float).double_value(&[]), 12.0);
+
+        // Integer division truncates, preserving the kind.
+        tensor_type!(IntTensor, [my_param2, my_param3], Params, Kind::Int64);
+        let numerator = IntTensor::new(Tensor::from_slice(&[7i64; 6]).reshape([2, 3]), &params);
+        let denominator = IntTensor::new(Tensor::from_slice(&[2i64; 6]).reshape([2, 3]), &params);
+        let quotient = numerator.unwrap() / denominator.unwrap();
+        assert_eq!(quotient.kind(), Kind::Int64);
+        assert_eq!(quotient.int64_value(&[0, 0]), 3);
+    }
+
+    #[test]
+    #[should_panic(expected = "integer division on TensorType \"IntTensor\" failed")]
+    fn test_integer_division_by_zero() {
+        // As for Rust integers, dividing by zero panics.
+        let params = setup();
+        tensor_type!(IntTensor, [my_param3], Params, Kind::Int64);
+        let numerator = IntTensor::new(Tensor::from_slice(&[7i64, 8, 9]), &params).unwrap();
+        let denominator = IntTensor::new(Tensor::from_slice(&[1i64, 0, 1]), &params).unwrap();
+        let _ = numerator / denominator;
+    }
+
+    #[test]
+    #[should_panic(expected = "shape mismatch in Add on TensorType \"MyTensor\"")]
+    fn test_arithmetic_shape_mismatch() {
+        // Values created with different parameters may differ in size, which the operators reject.
+        let params = setup();
+        let other_params = Params {
+            my_param1: MyParam1(2),
+            ..setup()
+        };
+        let a = MyTensor::ones(&params, Device::Cpu).unwrap();
+        let b = MyTensor::ones(&other_params, Device::Cpu).unwrap();
+        let _ = &a + &b;
+    }
+
+    #[test]
+    fn test_checked_arithmetic() {
+        // Values of a rank-only type may differ in size, so it has checked methods instead of the
+        // operators. Sizes that broadcast give a value that's checked like new().
+        let params = setup();
+        tensor_type!(RankTensor, rank = 2, Params, Kind::Float);
+        let a = RankTensor::new(Tensor::ones([1, 3], (Kind::Float, Device::Cpu)), &params).unwrap();
+        let b = RankTensor::new(Tensor::ones([2, 3], (Kind::Float, Device::Cpu)), &params).unwrap();
+        let sum = a.checked_add(&b, &params).unwrap();
+        assert_eq!(sum.size(), &[2, 3]);
+        assert_eq!(sum.double_value(&[1, 2]), 2.0);
+        assert_eq!(
+            a.checked_sub(&b, &params).unwrap().double_value(&[0, 0]),
+            0.0
+        );
+        assert_eq!(
+            sum.checked_mul(&sum, &params)
+                .unwrap()
+                .double_value(&[0, 0]),
+            4.0
+        );
+        assert_eq!(
+            sum.checked_div(&b, &params).unwrap().double_value(&[0, 0]),
+            2.0
+        );
+
+        // Sizes that don't broadcast are an error rather than a panic.
+        let c = RankTensor::new(Tensor::ones([2, 4], (Kind::Float, Device::Cpu)), &params).unwrap();
+        assert!(matches!(
+            b.checked_add(&c, &params),
+            Err(TensorTypeError::Tch { .. })
+        ));
+
+        // So is integer division by zero, which the operator reports with a panic.
+        tensor_type!(IntTensor, [my_param3], Params, Kind::Int64);
+        let numerator = IntTensor::new(Tensor::from_slice(&[7i64, 8, 9]), &params).unwrap();
+        let denominator = IntTensor::new(Tensor::from_slice(&[2i64, 0, 2]), &params).unwrap();
+        match numerator.checked_div(&denominator, &params) {
+            Err(TensorTypeError::Tch { type_name, .. }) => assert_eq!(type_name, "IntTensor"),
+            _ => panic!("expected a Tch error"),
+        }
+    }
+
+    #[test]
+    fn test_reshape_to() {
+        let params = setup();
+        tensor_type!(FlatTensor, [my_param3, my_param2], Params, Kind::Float);
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let my_tensor = MyTensor::new(t, &params).unwrap();
+
+        // Reshape into another type with the same number of elements.
+        let flat: FlatTensor = my_tensor.reshape_to(&params).unwrap();
+        assert_eq!(flat.size(), &[3, 2]);
+
+        // It's an error to reshape into a type with a different number of elements.
+        tensor_type!(SmallTensor, [my_param2, my_param2], Params, Kind::Float);
+        match my_tensor.reshape_to::<SmallTensor>(&params) {
+            Err(TensorTypeError::NumelMismatch {
+                type_name,
+                expected,
+                found,
+            }) => {
+                if type_name != "SmallTensor" || expected != 4 || found != 6 {
+                    panic!("expected NumelMismatch, but unexpected type_name ({}), found ({:?}) or expected ({:?})", type_name, found, expected)
+                }
+            }
+            _ => panic!("expected NumelMismatch"),
+        };
+
+        // Types that only check their rank have no dimensions to reshape into.
+        tensor_type!(RankTensor, rank = 2, Params, Kind::Float);
+        assert!(matches!(
+            my_tensor.reshape_to::<RankTensor>(&params),
+            Err(TensorTypeError::UnknownDimensions { .. })
+        ));
+    }
+
+    #[test]
+    fn test_squeeze_unsqueeze() {
+        let params = setup();
+        tensor_type!(SeqTensor, [my_param2, my_param3], Params, Kind::Float);
+        let t = Tensor::randn([2, 3], (Kind::Float, Device::Cpu));
+        let seq = SeqTensor::new(t, &params).unwrap();
+
+        // Add the leading dimension of size my_param1 = 1.
+        let my_tensor: MyTensor = seq.unsqueeze_to(0, &params).unwrap();
+        assert_eq!(my_tensor.size(), &[1, 2, 3]);
+
+        // And remove it again.
+        let seq: SeqTensor = my_tensor.squeeze_to(0, &params).unwrap();
+        assert_eq!(seq.size(), &[2, 3]);
+
+        // The result is checked against the target type.
+        assert!(matches!(
+            seq.unsqueeze_to::<MyTensor>(2, &params),
+            Err(TensorTypeError::ShapeMismatch { .. })
+        ));
+
+        // An axis out of range is an error rather than a panic.
+        assert!(matches!(
+            seq.unsqueeze_to::<MyTensor>(4, &params),
+            Err(TensorTypeError::Tch { .. })
+        ));
+        assert!(matches!(
+            seq.squeeze_to::<MyTensor>(3, &params),
+            Err(TensorTypeError::Tch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_mean_sum_to() {
+        let params = setup();
+        tensor_type!(PooledTensor, [my_param1, my_param3], Params, Kind::Float);
+        let t = Tensor::ones([1, 2, 3], (Kind::Float, Device::Cpu));
+        let my_tensor = MyTensor::new(t, &params).unwrap();
+
+        // Reduce over my_param2 = 2.
+        let mean: PooledTensor = my_tensor.mean_to(1, &params).unwrap();
+        assert_eq!(mean.size(), &[1, 3]);
+        assert_eq!(mean.double_value(&[0, 0]), 1.0);
+        let sum: PooledTensor = my_tensor.sum_to(1, &params).unwrap();
+        assert_eq!(sum.size(), &[1, 3]);
+        assert_eq!(sum.double_value(&[0, 0]), 2.0);
+
+        // The result is checked against the target type.
+        assert!(matches!(
+            my_tensor.mean_to::<PooledTensor>(2, &params),
+            Err(TensorTypeError::ShapeMismatch { .. })
+        ));
+
+        // An axis out of range is an error rather than a panic.
+        assert!(matches!(
+            my_tensor.mean_to::<PooledTensor>(3, &params),
+            Err(TensorTypeError::Tch { .. })
+        ));
+        assert!(matches!(
+            my_tensor.sum_to::<PooledTensor>(-4, &params),
+            Err(TensorTypeError::Tch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_transpose_to() {
+        let params = setup();
+        tensor_type!(
+            TransposedTensor,
+            [my_param1, my_param3, my_param2],
+            Params,
+            Kind::Float
+        );
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let my_tensor = MyTensor::new(t, &params).unwrap();
+
+        let transposed: TransposedTensor = my_tensor.transpose_to(1, 2, &params).unwrap();
+        assert_eq!(transposed.size(), &[1, 3, 2]);
+        let back: MyTensor = transposed.transpose_to(-1, -2, &params).unwrap();
+        assert_eq!(back, my_tensor);
+
+        match my_tensor.transpose_to::<TransposedTensor>(0, 1, &params) {
+            Err(TensorTypeError::ShapeMismatch {
+                type_name,
+                expected,
+                found,
+                ..
+            }) => {
+                if type_name != "TransposedTensor"
+                    || expected != vec![1, 3, 2]
+                    || found != vec![2, 1, 3]
+                {
+                    panic!("expected ShapeMismatch, but unexpected type_name ({}), found ({:?}) or expected ({:?})", type_name, found, expected)
+                }
+            }
+            _ => panic!("expected ShapeMismatch"),
+        };
+
+        // An axis out of range is an error rather than a panic.
+        assert!(matches!(
+            my_tensor.transpose_to::<TransposedTensor>(1, 3, &params),
+            Err(TensorTypeError::Tch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_flatten_to() {
+        let params = setup();
+        tensor_type!(
+            FlatTensor,
+            [my_param1, my_param2 * my_param3],
+            Params,
+            Kind::Float
+        );
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let my_tensor = MyTensor::new(t, &params).unwrap();
+
+        let flat: FlatTensor = my_tensor.flatten_to(1, 2, &params).unwrap();
+        assert_eq!(flat.size(), &[1, 6]);
+
+        match my_tensor.flatten_to::<FlatTensor>(0, 1, &params) {
+            Err(TensorTypeError::ShapeMismatch {
+                expected, found, ..
+            }) => assert_eq!((expected, found), (vec![1, 6], vec![2, 3])),
+            _ => panic!("expected ShapeMismatch"),
+        };
+
+        // Axes out of range or out of order are an error rather than a panic.
+        assert!(matches!(
+            my_tensor.flatten_to::<FlatTensor>(1, 3, &params),
+            Err(TensorTypeError::Tch { .. })
+        ));
+        assert!(matches!(
+            my_tensor.flatten_to::<FlatTensor>(2, 1, &params),
+            Err(TensorTypeError::Tch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_expand_to() {
+        let params = setup();
+        tensor_type!(BiasTensor, [my_param3], Params, Kind::Float);
+        let bias = BiasTensor::new(Tensor::from_slice(&[1.0f32, 2.0, 3.0]), &params).unwrap();
+
+        let expanded: MyTensor = bias.expand_to(&params).unwrap();
+        assert_eq!(expanded.size(), &[1, 2, 3]);
+        assert_eq!(expanded.double_value(&[0, 1, 2]), 3.0);
+
+        // A tensor that can't be broadcast to the target size is an error.
+        tensor_type!(RowTensor, [my_param2], Params, Kind::Float);
+        let row = RowTensor::ones(&params, Device::Cpu).unwrap();
+        assert!(matches!(
+            row.expand_to::<MyTensor>(&params),
+            Err(TensorTypeError::ShapeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_permute_to() {
+        let params = setup();
+        tensor_type!(
+            PermutedTensor,
+            [my_param3, my_param1, my_param2],
+            Params,
+            Kind::Float
+        );
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let my_tensor = MyTensor::new(t, &params).unwrap();
+
+        let permuted: PermutedTensor = my_tensor.permute_to(&[2, 0, 1], &params).unwrap();
+        assert_eq!(permuted.size(), &[3, 1, 2]);
+        let back: MyTensor = permuted.permute_to(&[1, 2, 0], &params).unwrap();
+        assert_eq!(back, my_tensor);
+
+        // The wrong permutation gives the wrong shape.
+        assert!(matches!(
+            my_tensor.permute_to::<PermutedTensor>(&[2, 1, 0], &params),
+            Err(TensorTypeError::ShapeMismatch { .. })
+        ));
+        assert!(matches!(
+            my_tensor.permute_to::<PermutedTensor>(&[1, 0], &params),
+            Err(TensorTypeError::RankMismatch { .. })
+        ));
+
+        // Repeated or out of range dimensions are an error rather than a panic.
+        assert!(matches!(
+            my_tensor.permute_to::<PermutedTensor>(&[2, 2, 1], &params),
+            Err(TensorTypeError::Tch { .. })
+        ));
+        assert!(matches!(
+            my_tensor.permute_to::<PermutedTensor>(&[3, 0, 1], &params),
+            Err(TensorTypeError::Tch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_cat() {
+        let params = setup();
+        tensor_type!(RankTensor, rank = 3, Params, Kind::Float);
+        let items: Vec<RankTensor> = (0..3)
+            .map(|_| Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu)))
+            .map(|t| RankTensor::new(t, &params).unwrap())
+            .collect();
+
+        let joined = RankTensor::cat(&items, 0, &params).unwrap();
+        assert_eq!(joined.size(), &[3, 2, 3]);
+
+        // The result is checked, so concatenating along a fixed axis is an error.
+        let items: Vec<MyTensor> = (0..3)
+            .map(|_| Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu)))
+            .map(|t| MyTensor::new(t, &params).unwrap())
+            .collect();
+        assert!(matches!(
+            MyTensor::cat(&items, 0, &params),
+            Err(TensorTypeError::ShapeMismatch { .. })
+        ));
+
+        // An axis out of range is an error rather than a panic.
+        assert!(matches!(
+            MyTensor::cat(&items, 3, &params),
+            Err(TensorTypeError::Tch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_cat_empty() {
+        // There's no tensor to concatenate, so this is an error rather than a panic.
+        let params = setup();
+        match MyTensor::cat(&[], 0, &params) {
+            Err(error @ TensorTypeError::EmptyItems { .. }) => assert_eq!(
+                error.to_string(),
+                "no items on TensorType \"MyTensor\": at least one value is required"
+            ),
+            _ => panic!("expected EmptyItems"),
+        };
+    }
+
+    #[test]
+    fn test_stack() {
+        let params = setup();
+        tensor_type!(SeqTensor, [my_param2, my_param3], Params, Kind::Float);
+        let t = Tensor::randn([2, 3], (Kind::Float, Device::Cpu));
+        let items = vec![SeqTensor::new(t, &params).unwrap()];
+
+        // Stack the my_param1 = 1 items into a MyTensor.
+        let stacked: MyTensor = SeqTensor::stack(&items, 0, &params).unwrap();
+        assert_eq!(stacked.size(), &[1, 2, 3]);
+
+        // Items with differing shapes are rejected before stacking.
+        tensor_type!(RankTensor, rank = 2, Params, Kind::Float);
+        let items = vec![
+            RankTensor::new(Tensor::randn([2, 3], (Kind::Float, Device::Cpu)), &params).unwrap(),
+            RankTensor::new(Tensor::randn([2, 4], (Kind::Float, Device::Cpu)), &params).unwrap(),
+        ];
+        match RankTensor::stack::<MyTensor>(&items, 0, &params) {
+            Err(TensorTypeError::ShapeMismatch {
+                type_name,
+                expected,
+                found,
+                ..
+            }) => {
+                if type_name != "RankTensor" || expected != vec![2, 3] || found != vec![2, 4] {
+                    panic!("expected ShapeMismatch, but unexpected type_name ({}), found ({:?}) or expected ({:?})", type_name, found, expected)
+                }
+            }
+            _ => panic!("expected ShapeMismatch"),
+        };
+
+        // An axis out of range is an error rather than a panic.
+        let items = vec![SeqTensor::ones(&params, Device::Cpu).unwrap()];
+        assert!(matches!(
+            SeqTensor::stack::<MyTensor>(&items, 4, &params),
+            Err(TensorTypeError::Tch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_stack_empty() {
+        let params = setup();
+        tensor_type!(SeqTensor, [my_param2, my_param3], Params, Kind::Float);
+        assert!(matches!(
+            SeqTensor::stack::<MyTensor>(&[], 0, &params),
+            Err(TensorTypeError::EmptyItems { .. })
+        ));
+    }
+
+    #[test]
+    fn test_broadcast() {
+        let params = setup();
+        tensor_type!(
+            BiasTensor,
+            [my_param1, my_param2, my_param3],
+            Params,
+            Kind::Float,
+            broadcast = true
+        );
+        assert_eq!(
+            BiasTensor::OPTIONS,
+            TensorTypeOptions {
+                broadcast: true,
+                ..Default::default()
+            }
+        );
+        assert_eq!(MyTensor::OPTIONS, TensorTypeOptions::default());
+
+        // The trailing dimensions match, so the leading ones may be absent.
+        for size in [vec![3], vec![2, 3], vec![1, 2, 3]] {
+            let t = Tensor::randn(size.as_slice(), (Kind::Float, Device::Cpu));
+            let bias = BiasTensor::new(t, &params).unwrap();
+            assert_eq!(bias.size(), size);
+        }
+
+        // Mismatched trailing dimensions and extra leading dimensions are still rejected.
+        for size in [vec![2], vec![1, 3], vec![1, 1, 2, 3]] {
+            let t = Tensor::randn(size.as_slice(), (Kind::Float, Device::Cpu));
+            assert!(matches!(
+                BiasTensor::new(t, &params),
+                Err(TensorTypeError::ShapeMismatch { .. })
+            ));
+        }
+
+        tensor_type!(RankTensor, rank = 3, Params, Kind::Float, broadcast = true);
+        let t = Tensor::randn([5, 6], (Kind::Float, Device::Cpu));
+        assert!(RankTensor::new(t, &params).is_ok());
+        let t = Tensor::randn([1, 2, 3, 4], (Kind::Float, Device::Cpu));
+        assert!(matches!(
+            RankTensor::new(t, &params),
+            Err(TensorTypeError::RankMismatch { .. })
+        ));
     }
 
     #[test]
-    fn test_wrong_kind() {
+    fn test_requires_grad() {
         let params = setup();
+        tensor_type!(
+            Weight,
+            [my_param2, my_param3],
+            Params,
+            Kind::Float,
+            requires_grad = true
+        );
+        tensor_type!(
+            Input,
+            [my_param2, my_param3],
+            Params,
+            Kind::Float,
+            requires_grad = false
+        );
 
-        // It's an error to call new() with the wrong kind of tensor.
-        let t = Tensor::from_slice(&[1, 2, 3, 4, 5, 6])
-            .reshape([1, 2, 3])
-            .to_kind(Kind::Int64);
-        match MyTensor::new(t, &params) {
-            Err(TensorTypeError::KindMismatch {
+        let t = Tensor::randn([2, 3], (Kind::Float, Device::Cpu));
+        match Weight::new(t.shallow_clone(), &params) {
+            Err(TensorTypeError::GradMismatch {
                 type_name,
                 expected,
                 found,
             }) => {
-                if type_name != "MyTensor" || expected != Kind::Float || found != Kind::Int64 {
-                    panic!("expected ShapeMismatch, but unexpected type_name ({}), found ({:?}) or expected ({:?})", type_name, found, expected)
+                if type_name != "Weight" || !expected || found {
+                    panic!("expected GradMismatch, but unexpected type_name ({}), found ({}) or expected ({})", type_name, found, expected)
                 }
             }
-            _ => panic!("expected KindMismatch"),
+            _ => panic!("expected GradMismatch"),
         };
+        let mut input = Input::new(t, &params).unwrap();
+
+        // Created tensors require gradients if the type requires them.
+        let weight = Weight::randn(&params, Device::Cpu).unwrap();
+        assert!(weight.requires_grad());
+        assert!(weight.verify(&params).is_ok());
+
+        input.set_requires_grad(true);
+        assert!(input.requires_grad());
+        assert!(matches!(
+            input.verify(&params),
+            Err(TensorTypeError::GradMismatch { .. })
+        ));
+
+        // Without the option, requires_grad isn't checked.
+        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu)).set_requires_grad(true);
+        assert!(MyTensor::new(t, &params).is_ok());
     }
 
     #[test]
-    fn test_types() {
+    fn test_contiguous() {
         let params = setup();
-        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        tensor_type!(
+            KernelInput,
+            [my_param2, my_param3],
+            Params,
+            Kind::Float,
+            contiguous = true
+        );
+        let t = Tensor::randn([2, 3], (Kind::Float, Device::Cpu));
+        assert!(KernelInput::new(t, &params).is_ok());
+
+        // A transposed tensor is a non-contiguous view, which is rejected rather than copied.
+        let t = Tensor::randn([3, 2], (Kind::Float, Device::Cpu)).transpose(0, 1);
+        match KernelInput::new(t.shallow_clone(), &params) {
+            Err(TensorTypeError::NotContiguous { type_name }) => {
+                if type_name != "KernelInput" {
+                    panic!(
+                        "expected NotContiguous, but unexpected type_name ({})",
+                        type_name
+                    )
+                }
+            }
+            _ => panic!("expected NotContiguous"),
+        };
+        assert!(KernelInput::new(t.contiguous(), &params).is_ok());
+
+        // Without the option, contiguity isn't checked.
+        tensor_type!(AnyInput, [my_param2, my_param3], Params, Kind::Float);
+        assert!(AnyInput::new(t, &params).is_ok());
+    }
+
+    #[test]
+    fn test_constructors() {
+        let params = setup();
+        let zeros = MyTensor::zeros(&params, Device::Cpu).unwrap();
+        assert_eq!(zeros.size(), &[1, 2, 3]);
+        assert_eq!(zeros.kind(), Kind::Float);
+        assert_eq!(zeros.sum(Kind::Float).double_value(&[]), 0.0);
+
+        let ones = MyTensor::ones(&params, Device::Cpu).unwrap();
+        assert_eq!(ones.sum(Kind::Float).double_value(&[]), 6.0);
+
+        let randn = MyTensor::randn(&params, Device::Cpu).unwrap();
+        assert!(MyTensor::new(randn.into_inner(), &params).is_ok());
+
+        // A rank-only type has no dimensions to build a tensor from.
+        tensor_type!(RankTensor, rank = 3, Params, Kind::Float);
+        assert!(matches!(
+            RankTensor::zeros(&params, Device::Cpu),
+            Err(TensorTypeError::UnknownDimensions { .. })
+        ));
+    }
+
+    #[test]
+    fn test_from_flat_slice() {
+        let params = setup();
+        let data = vec![1.0f64, 2.0, 3.0, 4.0, 5.0, 6.0];
+        let my_tensor = MyTensor::from_flat_slice(&data, &params).unwrap();
+        assert_eq!(my_tensor.size(), &[1, 2, 3]);
+        assert_eq!(my_tensor.kind(), Kind::Float);
+        assert_eq!(my_tensor.double_value(&[0, 1, 2]), 6.0);
+
+        match MyTensor::from_flat_slice(&data[..5], &params) {
+            Err(TensorTypeError::NumelMismatch {
+                expected, found, ..
+            }) => assert_eq!((expected, found), (6, 5)),
+            _ => panic!("expected NumelMismatch"),
+        };
+    }
+
+    #[test]
+    fn test_to_flat_vec() {
+        let params = setup();
+        let t = Tensor::from_slice(&[1.5f32, -2.5, 3.0, 4.0, 5.0, 6.0]).reshape([1, 2, 3]);
         let my_tensor = MyTensor::new(t, &params).unwrap();
+        assert_eq!(
+            my_tensor.to_flat_vec_f64(),
+            vec![1.5, -2.5, 3.0, 4.0, 5.0, 6.0]
+        );
+        assert_eq!(my_tensor.to_flat_vec_i64(), vec![1, -2, 3, 4, 5, 6]);
 
-        fn type_of<T>(_: T) -> &'static str {
-            std::any::type_name::<T>()
+        tensor_type!(IntTensor, [my_param2], Params, Kind::Int64);
+        let int_tensor = IntTensor::new(Tensor::from_slice(&[7i64, 8]), &params).unwrap();
+        assert_eq!(int_tensor.to_flat_vec_i64(), vec![7, 8]);
+        assert_eq!(int_tensor.to_flat_vec_f64(), vec![7.0, 8.0]);
+    }
+
+    #[test]
+    fn test_get_element() {
+        let params = setup();
+        let t = Tensor::from_slice(&[1.5f32, -2.5, 3.0, 4.0, 5.0, 6.0]).reshape([1, 2, 3]);
+        let my_tensor = MyTensor::new(t, &params).unwrap();
+        let element = my_tensor.get_element(&[0, 1, 2]).unwrap();
+        assert_eq!(element.dim(), 0);
+        assert_eq!(element.double_value(&[]), 6.0);
+        assert_eq!(
+            my_tensor.get_element(&[0, 0, 1]).unwrap().double_value(&[]),
+            -2.5
+        );
+
+        match my_tensor.get_element(&[0, 1]) {
+            Err(TensorTypeError::RankMismatch {
+                expected_rank,
+                found_rank,
+                ..
+            }) => {
+                if expected_rank != 3 || found_rank != 2 {
+                    panic!("wrong ranks: {expected_rank}, {found_rank}");
+                }
+            }
+            _ => panic!("expected a RankMismatch error"),
         }
 
-        // The type of my_tensor is MyTensor.
-        assert_eq!(type_of(&my_tensor), "&tensor_types_tests::tests::MyTensor");
-        // The type of what it wraps is Tensor.
-        assert_eq!(type_of(&*my_tensor), "&tch::wrappers::tensor::Tensor");
+        // Negative indices count back from the end, and indices out of range are an error.
+        assert_eq!(
+            my_tensor
+                .get_element(&[0, -1, -3])
+                .unwrap()
+                .double_value(&[]),
+            4.0
+        );
+        for (indices, axis, index) in [([0, 2, 0], 1, 2), ([0, 0, -4], 2, -4)] {
+            match my_tensor.get_element(&indices) {
+                Err(TensorTypeError::IndexOutOfRange {
+                    type_name,
+                    axis: found_axis,
+                    index: found_index,
+                    ..
+                }) => assert_eq!(
+                    (type_name.as_str(), found_axis, found_index),
+                    ("MyTensor", axis, index)
+                ),
+                _ => panic!("expected an IndexOutOfRange error"),
+            }
+        }
     }
 
+    #[cfg(feature = "ndarray")]
     #[test]
-    fn test_accessors() {
+    fn test_from_ndarray() {
         let params = setup();
-        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let array = ndarray::Array::from_shape_vec((1, 2, 3), vec![1i32, 2, 3, 4, 5, 6]).unwrap();
+        let my_tensor = MyTensor::from_ndarray(&array, &params).unwrap();
+        assert_eq!(my_tensor.size(), &[1, 2, 3]);
+        // The elements are converted to the declared kind.
+        assert_eq!(my_tensor.kind(), Kind::Float);
+        assert_eq!(my_tensor.double_value(&[0, 1, 2]), 6.0);
+
+        let array = ndarray::ArrayD::<f64>::zeros(ndarray::IxDyn(&[1, 3, 2]));
+        assert!(matches!(
+            MyTensor::from_ndarray(&array, &params),
+            Err(TensorTypeError::ShapeMismatch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_serde() {
+        use serde::de::DeserializeSeed;
+
+        let params = setup();
+        let t = Tensor::from_slice(&[1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0]).reshape([1, 2, 3]);
         let my_tensor = MyTensor::new(t, &params).unwrap();
+        let json = serde_json::to_string(&my_tensor).unwrap();
+        assert_eq!(
+            json,
+            r#"{"kind":"Float","shape":[1,2,3],"data":{"Float":[1.0,2.0,3.0,4.0,5.0,6.0]}}"#
+        );
 
-        // Access the tensor using the tensor() function.
-        assert_eq!(my_tensor.tensor().size(), &[1, 2, 3]);
+        // Deserializing with a seed checks the tensor against the params.
+        let mut deserializer = serde_json::Deserializer::from_str(&json);
+        let restored = MyTensor::seed(&params)
+            .deserialize(&mut deserializer)
+            .unwrap();
+        assert_eq!(restored, my_tensor);
+        assert_eq!(restored.kind(), Kind::Float);
 
-        // Access the tensor using explicit deref.
-        assert_eq!((*my_tensor).size(), &[1, 2, 3]);
+        let json = r#"{"kind":"Float","shape":[2,3],"data":{"Float":[1,2,3,4,5,6]}}"#;
+        let mut deserializer = serde_json::Deserializer::from_str(json);
+        assert!(MyTensor::seed(&params)
+            .deserialize(&mut deserializer)
+            .is_err());
 
-        // Access the tensor using automatic deref.
-        assert_eq!(my_tensor.size(), &[1, 2, 3]);
+        // Plain deserialization is unchecked, so it must be followed by verify().
+        let unchecked: MyTensor = serde_json::from_str(json).unwrap();
+        assert!(unchecked.verify(&params).is_err());
 
-        // Unwrap the inner tensor.
-        let unwrapped_tensor = my_tensor.into_inner();
-        assert_eq!(unwrapped_tensor.size(), &[1, 2, 3]);
-        fn type_of<T>(_: T) -> &'static str {
-            std::any::type_name::<T>()
+        // Integer tensors keep their kind.
+        tensor_type!(IntTensor, [my_param2], Params, Kind::Int16);
+        let t = Tensor::from_slice(&[7i16, -8]);
+        let int_tensor = IntTensor::new(t, &params).unwrap();
+        let json = serde_json::to_string(&int_tensor).unwrap();
+        assert_eq!(
+            json,
+            r#"{"kind":"Int16","shape":[2],"data":{"Int":[7,-8]}}"#
+        );
+        let restored: IntTensor = serde_json::from_str(&json).unwrap();
+        assert!(restored.verify(&params).is_ok());
+    }
+
+    #[test]
+    fn test_serde_invalid_shape() {
+        // The shape is checked before the tensor is built from the data.
+        let json = r#"{"kind":"Float","shape":[-1,-6],"data":{"Float":[1,2,3,4,5,6]}}"#;
+        let err = serde_json::from_str::<MyTensor>(json).unwrap_err();
+        assert!(err.to_string().contains("negative dimension -1"));
+
+        let json = r#"{"kind":"Float","shape":[4294967296,4294967296],"data":{"Float":[]}}"#;
+        let err = serde_json::from_str::<MyTensor>(json).unwrap_err();
+        assert!(err.to_string().contains("dimension overflow"));
+
+        let json = r#"{"kind":"Float","shape":[1,2,3],"data":{"Float":[1,2,3]}}"#;
+        let err = serde_json::from_str::<MyTensor>(json).unwrap_err();
+        assert!(err
+            .to_string()
+            .contains("tensor data has 3 elements, but the shape [1, 2, 3] requires 6"));
+    }
+
+    #[test]
+    fn test_save_load() {
+        let params = setup();
+        let my_tensor = MyTensor::randn(&params, Device::Cpu).unwrap();
+        let path = std::env::temp_dir().join("tensor_types_test_save_load.pt");
+        my_tensor.save(&path).unwrap();
+
+        let loaded = MyTensor::load(&path, &params).unwrap();
+        assert_eq!(loaded, my_tensor);
+
+        // The loaded tensor is checked against the type.
+        tensor_type!(OtherTensor, [my_param2], Params, Kind::Float);
+        assert!(matches!(
+            OtherTensor::load(&path, &params),
+            Err(TensorTypeError::ShapeMismatch { .. })
+        ));
+        std::fs::remove_file(&path).unwrap();
+
+        assert!(matches!(
+            MyTensor::load(&path, &params),
+            Err(TensorTypeError::Io { .. })
+        ));
+    }
+
+    #[test]
+    fn test_chunk_to() {
+        let params = setup();
+        tensor_type!(
+            HalfTensor,
+            [my_param1, my_param1, my_param3],
+            Params,
+            Kind::Float
+        );
+        let my_tensor = MyTensor::randn(&params, Device::Cpu).unwrap();
+
+        // Split the my_param2 = 2 axis into two chunks.
+        let halves: Vec<HalfTensor> = my_tensor.chunk_to(2, 1, &params).unwrap();
+        assert_eq!(halves.len(), 2);
+        assert_eq!(halves[1].size(), &[1, 1, 3]);
+
+        // Each chunk is checked against the target type.
+        assert!(matches!(
+            my_tensor.chunk_to::<HalfTensor>(1, 1, &params),
+            Err(TensorTypeError::ShapeMismatch { .. })
+        ));
+
+        match my_tensor.chunk_to::<MyTensor>(2, -1, &params) {
+            Err(TensorTypeError::IndivisibleDimension {
+                type_name,
+                axis,
+                size,
+                chunks,
+            }) => {
+                if type_name != "MyTensor" || axis != 2 || size != 3 || chunks != 2 {
+                    panic!("expected IndivisibleDimension, but unexpected type_name ({}), axis ({}), size ({}) or chunks ({})", type_name, axis, size, chunks)
+                }
+            }
+            _ => panic!("expected IndivisibleDimension"),
+        };
+
+        // An axis out of range is an error rather than a panic.
+        for dim in [3, -4] {
+            match my_tensor.chunk_to::<HalfTensor>(2, dim, &params) {
+                Err(TensorTypeError::InvalidAxis {
+                    type_name,
+                    axis,
+                    rank,
+                }) => assert_eq!((type_name.as_str(), axis, rank), ("MyTensor", dim, 3)),
+                _ => panic!("expected InvalidAxis"),
+            };
         }
-        assert_eq!(type_of(&unwrapped_tensor), "&tch::wrappers::tensor::Tensor");
     }
 
     #[test]
-    fn test_clone() {
+    fn test_typed_error() {
+        type MyTensorError = TypedTensorError<MyTensor>;
+        fn make(t: Tensor, params: &Params) -> Result<MyTensor, MyTensorError> {
+            Ok(MyTensor::new(t, params)?)
+        }
+
         let params = setup();
-        tensor_type!(MyTensor2, [my_param2, my_param3], Params, Kind::Int);
-        let tensor = Tensor::from_slice(&[0, 1, 2, 3, 4, 5]).reshape([2, 3]);
-        let my_tensor2 = MyTensor2::new(tensor, &params).unwrap();
-        // clone() makes a clone of MyTensor with a shallow_clone of the wrapped tensor.
-        let cloned_wrapper = my_tensor2.clone(&params).unwrap();
+        let t = Tensor::zeros([1, 2, 3], (Kind::Float, Device::Cpu));
+        assert!(make(t, &params).is_ok());
 
-        assert_eq!((*cloned_wrapper).size(), vec![2, 3]);
+        let t = Tensor::zeros([3], (Kind::Float, Device::Cpu));
+        let err = make(t, &params).unwrap_err();
+        assert!(matches!(err.error, TensorTypeError::ShapeMismatch { .. }));
+        assert_eq!(err.to_string(), err.error.to_string());
+        assert!(format!("{:?}", err).contains("tensor_type: \"MyTensor\""));
     }
 
     #[test]
-    fn test_apply_fn() {
+    fn test_normalizers() {
         let params = setup();
-        let t = Tensor::randn([1, 2, 3], (Kind::Float, Device::Cpu));
+        let t = Tensor::from_slice(&[1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0]).reshape([1, 2, 3]);
         let my_tensor = MyTensor::new(t, &params).unwrap();
 
-        // Test the apply_fn() method.
-        let new_my_tensor = my_tensor.apply_fn(|t| t.fill(1.0), &params).unwrap();
-        assert_eq!(new_my_tensor.tensor().size(), &[1, 2, 3]);
-        let tensor_of_ones = Tensor::ones([1, 2, 3], (Kind::Float, Device::Cpu));
+        let probs = my_tensor.softmax(-1, &params).unwrap();
+        assert_eq!(probs.kind(), Kind::Float);
+        let sums = probs.sum_dim_intlist(-1, false, Kind::Float);
+        assert!(sums.allclose(
+            &Tensor::ones([1, 2], (Kind::Float, Device::Cpu)),
+            1e-5,
+            1e-5,
+            false
+        ));
+
+        let log_probs = my_tensor.log_softmax(-1, &params).unwrap();
+        assert!(log_probs.exp().allclose(&probs, 1e-5, 1e-5, false));
+
+        let normed = my_tensor.layer_norm(1e-5, &params).unwrap();
+        let means = normed.mean_dim(-1, false, Kind::Float);
+        assert!(means.allclose(
+            &Tensor::zeros([1, 2], (Kind::Float, Device::Cpu)),
+            1e-5,
+            1e-5,
+            false
+        ));
+    }
+
+    #[test]
+    fn test_activations() {
+        let params = setup();
+        let t = Tensor::from_slice(&[-2.0f32, -1.0, 0.0, 1.0, 2.0, 3.0]).reshape([1, 2, 3]);
+        let my_tensor = MyTensor::new(t, &params).unwrap();
+
+        let relu = my_tensor.relu(&params).unwrap();
+        assert_eq!(relu.to_flat_vec_f64(), vec![0.0, 0.0, 0.0, 1.0, 2.0, 3.0]);
+
+        let clamped = my_tensor.clamp(-1.0, 1.0, &params).unwrap();
         assert_eq!(
-            new_my_tensor
-                .eq_tensor(&tensor_of_ones)
-                .all()
-                .int64_value(&[]),
-            1
+            clamped.to_flat_vec_f64(),
+            vec![-1.0, -1.0, 0.0, 1.0, 1.0, 1.0]
         );
-        assert_eq!(
-            tensor_of_ones
-                .eq_tensor(&new_my_tensor)
-                .all()
-                .int64_value(&[]),
-            1
+
+        let sigmoid = my_tensor.sigmoid(&params).unwrap();
+        assert_eq!(sigmoid.double_value(&[0, 0, 2]), 0.5);
+
+        let gelu = my_tensor.gelu(&params).unwrap();
+        assert_eq!(gelu.double_value(&[0, 0, 2]), 0.0);
+        assert_eq!(gelu.kind(), Kind::Float);
+    }
+
+    #[test]
+    fn test_concat_channels() {
+        let params = setup();
+        tensor_type!(Left, [my_param1, my_param2, my_param2], Params, Kind::Float);
+        tensor_type!(
+            Right,
+            [my_param1, my_param2, my_param1],
+            Params,
+            Kind::Float
         );
+        let left = Left::ones(&params, Device::Cpu).unwrap();
+        let right = Right::zeros(&params, Device::Cpu).unwrap();
+        let both: MyTensor = left.concat_channels(&right, -1, &params).unwrap();
+        assert_eq!(both.size(), &[1, 2, 3]);
+        assert_eq!(both.double_value(&[0, 0, 2]), 0.0);
 
-        // But it's an error if the function given to apply_fn changes the size of the wrapped tensor.
-        // The tensor shape is fixed and is checked.
-        match my_tensor.apply_fn(|t| t.transpose(1, 2), &params) {
-            Err(TensorTypeError::ShapeMismatch {
+        // The other axes must match.
+        match left.concat_channels::<Right, MyTensor>(&right, 1, &params) {
+            Err(TensorTypeError::AxisMismatch {
                 type_name,
+                axis,
                 expected,
                 found,
             }) => {
-                if type_name != "MyTensor" || expected != vec![1, 2, 3] || found != vec![1, 3, 2] {
-                    panic!("expected ShapeMismatch, but unexpected type_name ({}), found ({:?}) or expected ({:?})", type_name, found, expected)
+                if type_name != "Left" || axis != 2 || expected != 1 || found != 2 {
+                    panic!("expected AxisMismatch, but unexpected type_name ({}), axis ({}), expected ({}) or found ({})", type_name, axis, expected, found)
                 }
             }
-            _ => panic!("expected ShapeMismatch"),
+            _ => panic!("expected AxisMismatch"),
+        };
+
+        tensor_type!(Flat, [my_param2], Params, Kind::Float);
+        let flat = Flat::ones(&params, Device::Cpu).unwrap();
+        assert!(matches!(
+            left.concat_channels::<Flat, MyTensor>(&flat, -1, &params),
+            Err(TensorTypeError::RankMismatch { .. })
+        ));
+
+        // An axis out of range is an error rather than a panic.
+        assert!(matches!(
+            left.concat_channels::<Left, MyTensor>(&left, 3, &params),
+            Err(TensorTypeError::Tch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_matmul() {
+        let params = setup();
+        tensor_type!(Bmk, [my_param1, my_param2, my_param3], Params, Kind::Float);
+        tensor_type!(Bkn, [my_param1, my_param3, my_param2], Params, Kind::Float);
+        tensor_type!(Bmn, [my_param1, my_param2, my_param2], Params, Kind::Float);
+        let lhs = Bmk::ones(&params, Device::Cpu).unwrap();
+        let rhs = Bkn::ones(&params, Device::Cpu).unwrap();
+        let out: Bmn = lhs.matmul(&rhs, &params).unwrap();
+        assert_eq!(out.size(), &[1, 2, 2]);
+        assert_eq!(out.double_value(&[0, 0, 0]), 3.0);
+
+        // The contracted dimensions must match.
+        match lhs.matmul::<Bmk, Bmn>(&lhs, &params) {
+            Err(TensorTypeError::AxisMismatch {
+                type_name,
+                axis,
+                expected,
+                found,
+            }) => {
+                if type_name != "Bmk" || axis != 2 || expected != 2 || found != 3 {
+                    panic!("expected AxisMismatch, but unexpected type_name ({}), axis ({}), expected ({}) or found ({})", type_name, axis, expected, found)
+                }
+            }
+            _ => panic!("expected AxisMismatch"),
+        };
+
+        // The result must match the output type.
+        assert!(matches!(
+            lhs.matmul::<Bkn, Bmk>(&rhs, &params),
+            Err(TensorTypeError::ShapeMismatch { .. })
+        ));
+
+        // A scalar can't be multiplied, which is an error rather than a panic.
+        tensor_type!(Scalar, [], Params, Kind::Float);
+        let scalar = Scalar::ones(&params, Device::Cpu).unwrap();
+        assert!(matches!(
+            lhs.matmul::<Scalar, Bmn>(&scalar, &params),
+            Err(TensorTypeError::Tch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_iter_dim0() {
+        let params = setup();
+        tensor_type!(SeqTensor, [my_param2, my_param3], Params, Kind::Float);
+        let t = Tensor::from_slice(&[1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0]).reshape([1, 2, 3]);
+        let my_tensor = MyTensor::new(t, &params).unwrap();
+        let slices: Vec<Tensor> = my_tensor.iter_dim0().unwrap().collect();
+        assert_eq!(slices.len(), 1);
+        assert_eq!(slices[0].size(), &[2, 3]);
+
+        let samples: Vec<SeqTensor> = my_tensor
+            .iter_dim0_as(&params)
+            .collect::<Result<_, _>>()
+            .unwrap();
+        assert_eq!(samples.len(), 1);
+        assert_eq!(samples[0].double_value(&[1, 2]), 6.0);
+
+        // Each slice is checked against the target type.
+        assert!(my_tensor
+            .iter_dim0_as::<MyTensor>(&params)
+            .all(|sample| matches!(sample, Err(TensorTypeError::ShapeMismatch { .. }))));
+
+        // A scalar has no leading dimension, which is an error rather than a panic.
+        tensor_type!(Scalar, [], Params, Kind::Float);
+        let scalar = Scalar::ones(&params, Device::Cpu).unwrap();
+        assert!(matches!(
+            scalar.iter_dim0().map(|slices| slices.count()),
+            Err(TensorTypeError::Tch { .. })
+        ));
+        let samples: Vec<_> = scalar.iter_dim0_as::<Scalar>(&params).collect();
+        assert!(matches!(samples[..], [Err(TensorTypeError::Tch { .. })]));
+        assert!(matches!(
+            scalar.map_batch(|sample| sample.copy(), &params),
+            Err(TensorTypeError::Tch { .. })
+        ));
+    }
+
+    #[test]
+    fn test_map_batch() {
+        pub struct BatchParams {
+            batch_size: i64,
+            cols: i64,
+        }
+        let params = BatchParams {
+            batch_size: 2,
+            cols: 3,
+        };
+        tensor_type!(Batch, [batch_size, cols], BatchParams, Kind::Float);
+        let t = Tensor::from_slice(&[1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0]).reshape([2, 3]);
+        let batch = Batch::new(t, &params).unwrap();
+
+        let flipped = batch.map_batch(|sample| sample.flip([0]), &params).unwrap();
+        assert_eq!(flipped.double_value(&[0, 0]), 3.0);
+        assert_eq!(flipped.double_value(&[1, 0]), 6.0);
+
+        // Samples of differing shapes can't be stacked.
+        let result = batch.map_batch(
+            |sample| match sample.double_value(&[0]) {
+                1.0 => sample.narrow(0, 0, 2),
+                _ => sample.copy(),
+            },
+            &params,
+        );
+        assert!(matches!(result, Err(TensorTypeError::ShapeMismatch { .. })));
+
+        // An empty batch has no samples, so the function isn't called.
+        tensor_type!(RankTensor, rank = 2, BatchParams, Kind::Float);
+        let empty =
+            RankTensor::new(Tensor::zeros([0, 3], (Kind::Float, Device::Cpu)), &params).unwrap();
+        let mapped = empty
+            .map_batch(|_| panic!("no samples to map"), &params)
+            .unwrap();
+        assert_eq!(mapped.size(), &[0, 3]);
+    }
+
+    #[test]
+    fn test_assert_same_axis() {
+        let params = setup();
+        tensor_type!(SeqTensor, [my_param2, my_param3], Params, Kind::Float);
+        let my_tensor = MyTensor::new(
+            Tensor::zeros([1, 2, 3], (Kind::Float, Device::Cpu)),
+            &params,
+        )
+        .unwrap();
+        let seq_tensor = SeqTensor::zeros(&params, Device::Cpu).unwrap();
+        my_tensor.assert_same_axis(&seq_tensor, 1, 0).unwrap();
+        my_tensor.assert_same_axis(&seq_tensor, 2, 1).unwrap();
+
+        match my_tensor.assert_same_axis(&seq_tensor, 1, 1) {
+            Err(TensorTypeError::AxisMismatch {
+                type_name,
+                axis,
+                expected,
+                found,
+            }) => {
+                if type_name != "MyTensor" || axis != 1 || expected != 3 || found != 2 {
+                    panic!("expected AxisMismatch, but unexpected type_name ({}), axis ({}), expected ({}) or found ({})", type_name, axis, expected, found)
+                }
+            }
+            _ => panic!("expected AxisMismatch"),
+        };
+
+        // An axis out of range is reported on the type whose tensor lacks it.
+        match my_tensor.assert_same_axis(&seq_tensor, 3, 0) {
+            Err(TensorTypeError::InvalidAxis {
+                type_name,
+                axis,
+                rank,
+            }) => assert_eq!((type_name.as_str(), axis, rank), ("MyTensor", 3, 3)),
+            _ => panic!("expected InvalidAxis"),
+        };
+        match my_tensor.assert_same_axis(&seq_tensor, 0, 2) {
+            Err(TensorTypeError::InvalidAxis {
+                type_name,
+                axis,
+                rank,
+            }) => assert_eq!((type_name.as_str(), axis, rank), ("SeqTensor", 2, 2)),
+            _ => panic!("expected InvalidAxis"),
+        };
+    }
+
+    #[test]
+    fn test_assert_same_device() {
+        let params = setup();
+        tensor_type!(RowTensor, [my_param3], Params, Kind::Float);
+        let my_tensor = MyTensor::zeros(&params, Device::Cpu).unwrap();
+        let row = RowTensor::zeros(&params, Device::Cpu).unwrap();
+        assert!(my_tensor.assert_same_device(&row).is_ok());
+
+        let err = TensorTypeError::DeviceMismatch {
+            type_name: "MyTensor".to_string(),
+            expected: Device::Cuda(0),
+            found: Device::Cpu,
         };
+        assert_eq!(
+            err.to_string(),
+            "device mismatch on TensorType \"MyTensor\": expected Cuda(0), found Cpu"
+        );
+    }
+
+    #[test]
+    fn test_equality() {
+        let params = setup();
+        let t = Tensor::from_slice(&[1.0f32, 2.0, 3.0, 4.0, 5.0, 6.0]).reshape([1, 2, 3]);
+        let a = MyTensor::new(t, &params).unwrap();
+        let b = a.clone(&params).unwrap();
+        assert_eq!(a, b);
+
+        let c = a.apply_fn(|t| t + 1e-4, &params).unwrap();
+        assert_ne!(a, c);
+        assert!(a.approx_eq(&c, 1e-3));
+        assert!(!a.approx_eq(&c, 1e-5));
+
+        // Values of different kinds are unequal rather than compared.
+        tensor_type!(
+            #[partial_eq]
+            MixedTensor,
+            [my_param1, my_param2, my_param3],
+            Params,
+            [Kind::Float, Kind::Double]
+        );
+        let float = MixedTensor::ones(&params, Device::Cpu).unwrap();
+        let double = float
+            .to_kind_as::<MixedTensor>(Kind::Double, &params)
+            .unwrap();
+        assert_ne!(float, double);
+        assert!(!float.approx_eq(&double, 1e-3));
+
+        // The markers may be given in either order.
+        tensor_type!(
+            #[public_tensor]
+            #[partial_eq]
+            PublicEqTensor,
+            [my_param3],
+            Params,
+            Kind::Float
+        );
+        let t = PublicEqTensor::zeros(&params, Device::Cpu).unwrap();
+        assert_eq!(t, PublicEqTensor::zeros(&params, Device::Cpu).unwrap());
+
+        // Without #[partial_eq], a type can implement PartialEq itself.
+        tensor_type!(ShapeEqTensor, [my_param3], Params, Kind::Float);
+        impl PartialEq for ShapeEqTensor {
+            fn eq(&self, other: &Self) -> bool {
+                self.size() == other.size()
+            }
+        }
+        let zeros = ShapeEqTensor::zeros(&params, Device::Cpu).unwrap();
+        assert_eq!(zeros, ShapeEqTensor::ones(&params, Device::Cpu).unwrap());
     }
 
     #[test]